@@ -0,0 +1,221 @@
+//! Timings for the collection operations the GUI leans on, over a collection
+//! large enough for slow paths to show. Run with `cargo bench`.
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use ranki::collection::storage::{self, Format, JsonStore, Storage};
+use ranki::collection::{Action, Collection, CollectionService, Profiles, Query, SendData};
+
+const DECKS: u32 = 100;
+const CARDS_PER_DECK: u32 = 1_000;
+/// Cards in the single deck of the second collection, where an edit to a deck
+/// shared with a snapshot costs the most
+const BIG_DECK: u32 = 100_000;
+
+fn main() {
+    let (mut c, took) = time(build_collection);
+    println!(
+        "{} cards in {} decks built in {:?}",
+        DECKS * CARDS_PER_DECK,
+        DECKS,
+        took
+    );
+
+    // Answer half of every deck so that due dates are spread out, as they are
+    // in a collection that has been studied for a while
+    let uids: Vec<Uuid> = c
+        .decks
+        .iter()
+        .flat_map(|deck| deck.cards.iter().map(|card| card.uid))
+        .collect();
+    let results: Vec<(Uuid, Option<bool>)> = c
+        .decks
+        .iter()
+        .flat_map(|deck| deck.cards[..deck.cards.len() / 2].iter().map(|card| (card.uid, Some(true))))
+        .collect();
+    let count = results.len();
+    let (_, took) = time(|| c.process_results(results).expect("processing results"));
+    report("process quiz result", took, count);
+
+    let (_, took) = time(|| {
+        for deck_id in 0..DECKS {
+            c.deck_pos_by_id(deck_id).expect("deck exists");
+        }
+    });
+    report("find deck by id", took, DECKS as usize);
+
+    let (_, took) = time(|| {
+        for uid in uids.iter() {
+            c.card_by_uid(*uid).expect("card exists");
+        }
+    });
+    report("find card by uid", took, uids.len());
+
+    let (quiz, took) = time(|| c.get_quiz(None));
+    println!("full quiz of {} due cards: {:?}", quiz.len(), took);
+
+    let (_, took) = time(|| {
+        for deck_id in 0..DECKS {
+            c.due_count(deck_id);
+        }
+    });
+    report("due count of a deck", took, DECKS as usize);
+
+    let query = Query::parse("tag:verbs is:due").expect("valid query");
+    let (found, took) = time(|| c.search(&query).len());
+    println!("search matching {} cards: {:?}", found, took);
+
+    let edited = c.decks[50].cards[500].uid;
+    let (_, took) = time(|| c.edit_card(edited, "q".into(), "a".into(), "".into()));
+    println!("edit card: {:?}", took);
+
+    let (_, took) = time(|| c.clone());
+    println!("clone collection for an update: {:?}", took);
+
+    edit_to_update(&c, 50);
+
+    // Only decks changed since the last save are written to the database
+    let dir = std::env::temp_dir().join(format!("ranki-bench-sqlite-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut store = storage::open(Some("sqlite"), &dir, None).expect("opening");
+    let (_, took) = time(|| store.save(&c).expect("saving"));
+    println!("save to SQLite: {:?}", took);
+    c.edit_card(edited, "q2".into(), "a".into(), "".into())
+        .expect("editing card");
+    let (_, took) = time(|| store.save(&c).expect("saving"));
+    println!("save an edited card to SQLite: {:?}", took);
+    std::fs::remove_dir_all(&dir).ok();
+
+    for format in [Format::Json, Format::Compact].iter() {
+        let path = std::env::temp_dir().join(format!("ranki-bench-{:?}.conf", format));
+        let mut store = JsonStore::new(&path).with_format(*format);
+        let (_, took) = time(|| store.save(&c).expect("saving"));
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        println!("save as {:?}: {:?}, {} bytes", format, took, size);
+        let (_, took) = time(|| store.load().expect("loading"));
+        println!("load from {:?}: {:?}", format, took);
+        std::fs::remove_file(&path).ok();
+    }
+
+    big_deck();
+}
+
+// Times edits to a collection of a single large deck, which is copied whole
+// when it's still shared with a snapshot kept for undo
+fn big_deck() {
+    let (mut c, took) = time(|| build_deck(BIG_DECK));
+    println!("{} cards in one deck built in {:?}", BIG_DECK, took);
+
+    let edited = c.decks[0].cards[BIG_DECK as usize / 2].uid;
+    let (_, took) = time(|| c.edit_card(edited, "q".into(), "a".into(), "".into()));
+    println!("edit card in the big deck: {:?}", took);
+
+    let snapshot = c.clone();
+    let (_, took) = time(|| c.edit_card(edited, "q2".into(), "a".into(), "".into()));
+    println!("edit card in the big deck shared with a snapshot: {:?}", took);
+    drop(snapshot);
+
+    edit_to_update(&c, 0);
+}
+
+// Times edits from being sent to the service to the edited collection coming
+// back, as the window sees them, which takes in the snapshot kept for undo
+fn edit_to_update(c: &Collection, deck_pos: usize) {
+    const EDITS: u32 = 20;
+    let data = std::env::temp_dir().join(format!("ranki-bench-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&data);
+    let profiles = Profiles::open(&data).expect("opening profiles");
+    let profile = profiles.last().expect("default profile");
+    std::fs::create_dir_all(profiles.dir_of(profile)).expect("creating profile");
+    JsonStore::new(profiles.dir_of(profile).join(JsonStore::FILE))
+        .save(c)
+        .expect("saving collection");
+    let (update_tx, updates) = mpsc::channel();
+    let mut service = CollectionService::new(update_tx, None, None, profiles, profile)
+        .expect("opening collection");
+    let (tx, rx) = mpsc::channel();
+    let listening = thread::spawn(move || service.listen(rx));
+    updates.try_iter().for_each(drop);
+
+    let mut took = Duration::default();
+    for edit in 0..EDITS {
+        let question = format!("Edited question {}", edit);
+        let start = Instant::now();
+        let uid = c.decks[deck_pos].cards[edit as usize].uid;
+        tx.send(Action::EditCard(uid, question, "a".into(), "".into()))
+            .expect("sending edit");
+        wait_for(&updates, |data| matches!(data, SendData::Collection(_)));
+        took += start.elapsed();
+        // Wait out the save before the next edit, so only the update is timed
+        tx.send(Action::ListProfiles).expect("sending request");
+        wait_for(&updates, |data| matches!(data, SendData::Profiles(_)));
+    }
+    let deck = &c.decks[deck_pos];
+    let name = format!("edit to update in a deck of {}", deck.cards.len());
+    report(&name, took, EDITS as usize);
+
+    tx.send(Action::Quit).expect("sending quit");
+    listening.join().expect("service finished");
+    std::fs::remove_dir_all(&data).ok();
+}
+
+fn wait_for(updates: &mpsc::Receiver<SendData>, wanted: impl Fn(&SendData) -> bool) {
+    while !wanted(&updates.recv().expect("service running")) {}
+}
+
+fn build_collection() -> Collection {
+    let mut c = Collection::new();
+    for deck_id in 0..DECKS {
+        c.add_deck(&format!("Deck {}", deck_id));
+        for card_id in 0..CARDS_PER_DECK {
+            c.add_card(
+                deck_id,
+                format!("Question {} of deck {}", card_id, deck_id),
+                format!("Answer {}", card_id),
+                String::new(),
+            )
+            .expect("adding card");
+            if card_id % 10 == 0 {
+                let uid = c.decks[deck_id as usize].cards.last().expect("card added").uid;
+                c.set_tags(uid, vec!["verbs".to_string()])
+                    .expect("tagging card");
+            }
+        }
+    }
+    c
+}
+
+fn build_deck(cards: u32) -> Collection {
+    let mut c = Collection::new();
+    c.add_deck("Big Deck");
+    for card_id in 0..cards {
+        c.add_card(
+            0,
+            format!("Question {}", card_id),
+            format!("Answer {}", card_id),
+            String::new(),
+        )
+        .expect("adding card");
+    }
+    c
+}
+
+fn time<T, F: FnOnce() -> T>(f: F) -> (T, Duration) {
+    let start = Instant::now();
+    let result = f();
+    (result, start.elapsed())
+}
+
+fn report(name: &str, took: Duration, count: usize) {
+    println!(
+        "{}: {:?} for {}, {:?} each",
+        name,
+        took,
+        count,
+        took / count.max(1) as u32
+    );
+}
@@ -0,0 +1,261 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::crypt::Cipher;
+use super::data::Collection;
+use super::storage::{Format, JsonStore, ReadCollection, Storage};
+
+const DAY_SECS: u64 = 86400;
+
+/// How often backups of the collection are taken and how many are kept
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct BackupSettings {
+    /// Number of most recent backups always kept
+    pub keep: usize,
+    /// Minutes of changes to the collection between backups
+    pub interval_minutes: u32,
+    /// Number of days, and then weeks, beyond the most recent backups for
+    /// which the newest backup of each is kept
+    pub daily: usize,
+    pub weekly: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        BackupSettings {
+            keep: 10,
+            interval_minutes: 30,
+            daily: 7,
+            weekly: 4,
+        }
+    }
+}
+
+/// A backup on disk, with enough about it to judge whether to restore it
+#[derive(Clone, Debug)]
+pub struct BackupInfo {
+    pub name: String,
+    pub taken: SystemTime,
+    pub decks: usize,
+    pub cards: usize,
+}
+
+/// Timestamped copies of the collection, kept as JSON files in a directory
+pub struct Backups {
+    dir: PathBuf,
+    last: Option<SystemTime>,
+    /// Backups of an encrypted collection are encrypted the same way
+    cipher: Option<Cipher>,
+    /// And written in the collection's format
+    format: Format,
+}
+
+impl Backups {
+    pub const DIR: &'static str = "backups";
+
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Backups {
+            dir: dir.into(),
+            last: None,
+            cipher: None,
+            format: Format::Json,
+        }
+    }
+
+    pub fn set_cipher(&mut self, cipher: Option<Cipher>) {
+        self.cipher = cipher;
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Whether enough time has passed since the last backup to take another
+    pub fn due(&self, settings: &BackupSettings) -> bool {
+        let interval = Duration::from_secs(settings.interval_minutes as u64 * 60);
+        match self.last {
+            Some(last) => last + interval <= SystemTime::now(),
+            None => true,
+        }
+    }
+
+    /// Writes a backup of the collection, then thins out the old ones
+    pub fn write(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let now = SystemTime::now();
+        // Two backups taken in the same millisecond mustn't overwrite each other
+        let mut taken = now;
+        while self.dir.join(backup_name(taken, self.format)).exists() {
+            taken += Duration::from_millis(1);
+        }
+        let mut store = JsonStore::new(self.dir.join(backup_name(taken, self.format)))
+            .with_cipher(self.cipher.clone())
+            .with_format(self.format);
+        store.set_encrypted(self.cipher.is_some())?;
+        store.save(collection)?;
+        self.last = Some(now);
+        self.thin(&collection.backup_settings)
+    }
+
+    /// Rewrites the backups encrypted differently from new ones, after the
+    /// collection was encrypted or decrypted. `old` is what the collection was
+    /// encrypted with before, if it was.
+    pub fn reencrypt(&mut self, old: Option<Cipher>) -> Result<(), Box<dyn Error>> {
+        let mut reading = old.or_else(|| self.cipher.clone());
+        for (name, _) in self.names()? {
+            let path = self.dir.join(&name);
+            let read = match JsonStore::read(&path, reading.as_mut()) {
+                Ok(read) => read,
+                Err(e) => {
+                    println!("error reading backup {}, left it as it was: {}", name, e);
+                    continue;
+                }
+            };
+            if read.encrypted == self.cipher.is_some() {
+                continue;
+            }
+            let mut store = JsonStore::new(&path)
+                .with_cipher(self.cipher.clone())
+                .with_format(read.format);
+            store.set_encrypted(self.cipher.is_some())?;
+            store.overwrite(&read.collection)?;
+        }
+        Ok(())
+    }
+
+    /// Lists the backups on disk, newest first
+    pub fn list(&mut self) -> Result<Vec<BackupInfo>, Box<dyn Error>> {
+        let mut backups = vec![];
+        for (name, taken) in self.names()? {
+            match self.load(&name) {
+                Ok(collection) => backups.push(BackupInfo {
+                    name,
+                    taken,
+                    decks: collection.decks.len(),
+                    cards: collection.decks.iter().map(|deck| deck.cards.len()).sum(),
+                }),
+                Err(e) => println!("error reading backup {}: {}", name, e),
+            }
+        }
+        Ok(backups)
+    }
+
+    pub fn load(&mut self, name: &str) -> Result<Collection, Box<dyn Error>> {
+        Ok(self.read(name)?.collection)
+    }
+
+    /// The newest backup that can be read, along with its name
+    pub(super) fn newest(&mut self) -> Option<(String, ReadCollection)> {
+        for (name, _) in self.names().ok()? {
+            match self.read(&name) {
+                Ok(read) => return Some((name, read)),
+                Err(e) => println!("error reading backup {}: {}", name, e),
+            }
+        }
+        None
+    }
+
+    /// When the newest backup was taken, whether or not it can be read
+    pub(super) fn newest_taken(&self) -> Option<SystemTime> {
+        let names = self.names().ok()?;
+        names.first().map(|(_, taken)| *taken)
+    }
+
+    fn read(&mut self, name: &str) -> Result<ReadCollection, Box<dyn Error>> {
+        if parse_name(name).is_none() {
+            return Err(format!("{} is not the name of a backup", name).into());
+        }
+        JsonStore::read(&self.dir.join(name), self.cipher.as_mut())
+    }
+
+    // Keeps the most recent backups, then the newest backup of each of the
+    // following days and weeks, and deletes the rest
+    fn thin(&self, settings: &BackupSettings) -> Result<(), Box<dyn Error>> {
+        let now = secs_since_epoch(SystemTime::now());
+        let mut days = HashSet::new();
+        let mut weeks = HashSet::new();
+        for (idx, (name, taken)) in self.names()?.into_iter().enumerate() {
+            if idx < settings.keep {
+                continue;
+            }
+            let secs = secs_since_epoch(taken);
+            let age_days = (now.saturating_sub(secs) / DAY_SECS) as usize;
+            let keep = if age_days < settings.daily {
+                days.insert(secs / DAY_SECS)
+            } else if age_days < settings.daily + settings.weekly * 7 {
+                weeks.insert(secs / (DAY_SECS * 7))
+            } else {
+                false
+            };
+            if !keep {
+                fs::remove_file(self.dir.join(&name))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Names of the backups in the directory along with when they were taken,
+    // newest first
+    fn names(&self) -> Result<Vec<(String, SystemTime)>, Box<dyn Error>> {
+        let mut names = vec![];
+        if !self.dir.exists() {
+            return Ok(names);
+        }
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(taken) = parse_name(&name) {
+                names.push((name, taken));
+            }
+        }
+        names.sort_by_key(|(_, taken)| Reverse(*taken));
+        Ok(names)
+    }
+}
+
+// Names a backup after when it was taken, to the millisecond, with an
+// extension saying whether it's compressed
+fn backup_name(taken: SystemTime, format: Format) -> String {
+    let since = taken.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let extension = match format {
+        Format::Json => "json",
+        Format::Compact => "json.gz",
+    };
+    format!(
+        "backup-{}.{:03}.{}",
+        since.as_secs(),
+        since.subsec_millis(),
+        extension
+    )
+}
+
+/// Whether a file is named the way backups are
+pub(super) fn is_backup_name(name: &str) -> bool {
+    parse_name(name).is_some()
+}
+
+// The time a backup was taken, going by its file name. Older backups were
+// named to the second.
+fn parse_name(name: &str) -> Option<SystemTime> {
+    let name = name.strip_prefix("backup-")?;
+    let stamp = name
+        .strip_suffix(".json.gz")
+        .or_else(|| name.strip_suffix(".json"))?;
+    let (secs, millis) = match stamp.split_once('.') {
+        Some((secs, millis)) => (secs, millis.parse::<u64>().ok()?),
+        None => (stamp, 0),
+    };
+    let secs = secs.parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis))
+}
+
+fn secs_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
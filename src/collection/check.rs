@@ -0,0 +1,378 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use super::data::{Collection, Deck};
+use super::search::Query;
+
+/// What checking a collection turned up: the problems it fixed, and the ones
+/// left alone because fixing them means deciding what the collection holds
+#[derive(Clone, Debug, Default)]
+pub struct CheckReport {
+    pub repaired: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+impl CheckReport {
+    pub fn is_clean(&self) -> bool {
+        self.repaired.is_empty() && self.problems.is_empty()
+    }
+}
+
+impl Collection {
+    /// Checks the collection against the invariants the rest of the code
+    /// relies on. Ids and uids that clash or would be handed out again are
+    /// renumbered, along with the references to them, so the collection holds
+    /// the same decks and cards as before. Clashes where it can't be told what
+    /// a reference was to, and anything else, are only reported.
+    pub fn check(&mut self) -> CheckReport {
+        let mut report = CheckReport::default();
+        let ambiguous = self.check_deck_ids(&mut report);
+        self.check_card_ids(&ambiguous, &mut report);
+        self.check_uids(&mut report);
+        self.check_borrowed(&mut report);
+        self.check_contents(&mut report);
+        self.reindex();
+        report
+    }
+
+    // Every deck, including those in the trash
+    fn all_decks(&self) -> impl Iterator<Item = &Deck> {
+        let trashed = self.trash.decks.iter().map(|t| &t.deck);
+        self.decks.iter().map(|deck| &**deck).chain(trashed)
+    }
+
+    // Every deck to be changed, copying any shared with other copies of the
+    // collection
+    fn all_decks_mut(&mut self) -> impl Iterator<Item = &mut Deck> {
+        let trashed = Arc::make_mut(&mut self.trash).decks.iter_mut();
+        let trashed = trashed.map(|t| &mut t.deck);
+        self.decks.iter_mut().map(Arc::make_mut).chain(trashed)
+    }
+
+    // Trashed decks keep their ids to be restored under, so they count too.
+    // Returns the ids shared by decks that cards refer to, as which of the
+    // decks those cards belong to can't be told.
+    fn check_deck_ids(&mut self, report: &mut CheckReport) -> HashSet<u32> {
+        // None once every id has been used
+        let mut next_id = Some(self.curr_deck_id);
+        if let Some(max) = self.all_decks().map(|deck| deck.id).max() {
+            if self.curr_deck_id <= max {
+                next_id = max.checked_add(1);
+                match next_id {
+                    Some(next) => report.repaired.push(format!(
+                        "The next deck id was {}, already taken by deck {}, so it's now {}",
+                        self.curr_deck_id, max, next
+                    )),
+                    None => report.problems.push(format!(
+                        "The next deck id was {}, but deck ids run up to {}, the largest there \
+                         can be, so no more decks can be added",
+                        self.curr_deck_id, max
+                    )),
+                }
+            }
+        }
+
+        let borrowed = self.decks.iter().flat_map(|deck| deck.cards.iter());
+        let mut referenced: HashSet<u32> = borrowed
+            .filter_map(|card| card.home)
+            .map(|(deck_id, _)| deck_id)
+            .collect();
+        referenced.extend(self.trash.cards.iter().map(|trashed| trashed.deck_id));
+
+        let mut seen = HashSet::new();
+        let mut ambiguous = HashSet::new();
+        for deck in self.all_decks_mut() {
+            if seen.insert(deck.id) {
+                continue;
+            }
+            if referenced.contains(&deck.id) {
+                if ambiguous.insert(deck.id) {
+                    report.problems.push(format!(
+                        "Decks share id {}, which cards borrowed by filtered decks or in the \
+                         trash belong to, so which deck they go back to can't be told",
+                        deck.id
+                    ));
+                }
+                continue;
+            }
+            let new_id = match next_id {
+                Some(id) => id,
+                None => {
+                    report.problems.push(format!(
+                        "Deck \"{}\" shares id {} with another deck, and there are no ids left \
+                         to give it",
+                        deck.title, deck.id
+                    ));
+                    continue;
+                }
+            };
+            report.repaired.push(format!(
+                "Deck \"{}\" shared id {} with another deck, so it's now {}",
+                deck.title, deck.id, new_id
+            ));
+            deck.id = new_id;
+            next_id = new_id.checked_add(1);
+        }
+        if let Some(next) = next_id {
+            self.curr_deck_id = next;
+        }
+        ambiguous
+    }
+
+    // Besides its own cards, a deck's card ids are held by the cards it has
+    // lent to filtered decks and those in the trash, which go back under them.
+    // Two of those holding the same id would go back as the same card, so the
+    // later one is given a new id to go back under instead.
+    fn check_card_ids(&mut self, ambiguous: &HashSet<u32>, report: &mut CheckReport) {
+        // The id each deck hands out next, past every id it holds, or None if
+        // it holds the largest id there can be
+        let mut next: HashMap<u32, Option<u32>> = HashMap::new();
+        for deck in self.all_decks() {
+            let past_own = deck.cards.iter().map(|card| card.id.checked_add(1));
+            let past_own = past_own.fold(Some(0), later);
+            let next = next.entry(deck.id).or_insert(Some(0));
+            *next = later(later(*next, Some(deck.curr_card_id)), past_own);
+        }
+        let borrowed = self.decks.iter().flat_map(|deck| deck.cards.iter());
+        let homes = borrowed.filter_map(|card| card.home);
+        let trashed = self.trash.cards.iter().map(|t| (t.deck_id, t.card.id));
+        for (deck_id, card_id) in homes.chain(trashed) {
+            let next = next.entry(deck_id).or_insert(Some(0));
+            *next = later(*next, card_id.checked_add(1));
+        }
+
+        let mut taken: HashMap<u32, HashSet<u32>> = HashMap::new();
+        for deck in self.decks.iter_mut().map(Arc::make_mut) {
+            for card in deck.cards.iter_mut().map(Arc::make_mut) {
+                let (home, id) = match card.home {
+                    Some(home) if !ambiguous.contains(&home.0) => home,
+                    _ => continue,
+                };
+                if !taken.entry(home).or_default().insert(id) {
+                    let new_id = match take_id(&mut next, home) {
+                        Some(new_id) => new_id,
+                        None => {
+                            report.problems.push(format!(
+                                "Card {} of filtered deck \"{}\" was borrowed as card {} of deck \
+                                 {}, like another card, and there are no ids left to give it",
+                                card.id, deck.title, id, home
+                            ));
+                            continue;
+                        }
+                    };
+                    report.repaired.push(format!(
+                        "Card {} of filtered deck \"{}\" was borrowed as card {} of deck {}, like \
+                         another card, so it goes back as card {}",
+                        card.id, deck.title, id, home, new_id
+                    ));
+                    card.home = Some((home, new_id));
+                    taken.entry(home).or_default().insert(new_id);
+                }
+            }
+        }
+        for trashed in self.trash_mut().cards.iter_mut() {
+            let home = trashed.deck_id;
+            if ambiguous.contains(&home) {
+                continue;
+            }
+            if !taken.entry(home).or_default().insert(trashed.card.id) {
+                let new_id = match take_id(&mut next, home) {
+                    Some(new_id) => new_id,
+                    None => {
+                        report.problems.push(format!(
+                            "Card {} of deck \"{}\" in the trash shares its id with another \
+                             card, and there are no ids left to give it",
+                            trashed.card.id, trashed.deck_title
+                        ));
+                        continue;
+                    }
+                };
+                report.repaired.push(format!(
+                    "Card {} of deck \"{}\" in the trash shared its id with another card, so \
+                     it's now {}",
+                    trashed.card.id, trashed.deck_title, new_id
+                ));
+                trashed.card.id = new_id;
+                taken.entry(home).or_default().insert(new_id);
+            }
+        }
+
+        for deck in self.all_decks_mut() {
+            if ambiguous.contains(&deck.id) {
+                continue;
+            }
+            let taken = taken.entry(deck.id).or_default();
+            for card in deck.cards.iter_mut().map(Arc::make_mut) {
+                if !taken.insert(card.id) {
+                    let new_id = match take_id(&mut next, deck.id) {
+                        Some(new_id) => new_id,
+                        None => {
+                            report.problems.push(format!(
+                                "Card {} of deck \"{}\" shares its id with another card, and \
+                                 there are no ids left to give it",
+                                card.id, deck.title
+                            ));
+                            continue;
+                        }
+                    };
+                    report.repaired.push(format!(
+                        "Card {} of deck \"{}\" shared its id with another card, so it's now {}",
+                        card.id, deck.title, new_id
+                    ));
+                    card.id = new_id;
+                    taken.insert(new_id);
+                }
+            }
+            match next[&deck.id] {
+                Some(next) if deck.curr_card_id < next => {
+                    report.repaired.push(format!(
+                        "The next card id of deck \"{}\" was {}, already taken, so it's now {}",
+                        deck.title, deck.curr_card_id, next
+                    ));
+                    deck.curr_card_id = next;
+                }
+                Some(_) => {}
+                None => report.problems.push(format!(
+                    "Card ids of deck \"{}\" run up to {}, the largest there can be, so no more \
+                     cards can be added to it",
+                    deck.title,
+                    u32::MAX
+                )),
+            }
+        }
+    }
+
+    /// Gives every deck, card and note sharing a uid with an earlier one a new
+    /// uid
+    pub(super) fn check_uids(&mut self, report: &mut CheckReport) {
+        let mut seen = HashSet::new();
+        for deck in self.all_decks_mut() {
+            if !seen.insert(deck.uid) {
+                deck.uid = Uuid::new_v4();
+                report.repaired.push(format!(
+                    "Deck \"{}\" shared its uid with something else, so it has a new one",
+                    deck.title
+                ));
+            }
+            for card in deck.cards.iter_mut().map(Arc::make_mut) {
+                if !seen.insert(card.uid) {
+                    card.uid = Uuid::new_v4();
+                    report.repaired.push(format!(
+                        "Card {} of deck \"{}\" shared its uid with something else, so it has a \
+                         new one",
+                        card.id, deck.title
+                    ));
+                }
+                if !seen.insert(card.note_uid) {
+                    card.note_uid = Uuid::new_v4();
+                    report.repaired.push(format!(
+                        "The note of card {} of deck \"{}\" shared its uid with something else, \
+                         so it has a new one",
+                        card.id, deck.title
+                    ));
+                }
+            }
+        }
+        for trashed in self.trash_mut().cards.iter_mut() {
+            if !seen.insert(trashed.card.uid) {
+                trashed.card.uid = Uuid::new_v4();
+                report.repaired.push(format!(
+                    "Card {} of deck \"{}\" in the trash shared its uid with something else, so \
+                     it has a new one",
+                    trashed.card.id, trashed.deck_title
+                ));
+            }
+            if !seen.insert(trashed.card.note_uid) {
+                trashed.card.note_uid = Uuid::new_v4();
+                report.repaired.push(format!(
+                    "The note of card {} of deck \"{}\" in the trash shared its uid with \
+                     something else, so it has a new one",
+                    trashed.card.id, trashed.deck_title
+                ));
+            }
+        }
+    }
+
+    fn check_borrowed(&mut self, report: &mut CheckReport) {
+        let deck_ids: HashSet<u32> = self.decks.iter().map(|deck| deck.id).collect();
+        for deck in self.decks.iter_mut().map(Arc::make_mut) {
+            let filtered = deck.filter.is_some();
+            for card in deck.cards.iter_mut().map(Arc::make_mut) {
+                match card.home {
+                    // A card at home only needs to know where it was lent from
+                    // while it's away
+                    Some(_) if !filtered => {
+                        card.home = None;
+                        report.repaired.push(format!(
+                            "Card {} of deck \"{}\" was marked as borrowed by a filtered deck it \
+                             isn't in, so no longer is",
+                            card.id, deck.title
+                        ));
+                    }
+                    Some((home, _)) if !deck_ids.contains(&home) => {
+                        report.problems.push(format!(
+                            "Card {} of filtered deck \"{}\" was borrowed from deck {}, which \
+                             doesn't exist, so it has nowhere to go back to",
+                            card.id, deck.title, home
+                        ));
+                    }
+                    None if filtered => {
+                        report.problems.push(format!(
+                            "Card {} of filtered deck \"{}\" wasn't borrowed from any deck, so \
+                             it has nowhere to go back to",
+                            card.id, deck.title
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn check_contents(&self, report: &mut CheckReport) {
+        for deck in &self.decks {
+            if deck.title.trim().is_empty() {
+                report
+                    .problems
+                    .push(format!("Deck {} has no title", deck.id));
+            }
+            if let Some(filter) = &deck.filter {
+                if let Err(e) = Query::parse(&filter.query) {
+                    report.problems.push(format!(
+                        "The search of filtered deck \"{}\" can't be rebuilt: {}",
+                        deck.title, e
+                    ));
+                }
+            }
+            for card in &deck.cards {
+                if card.question.trim().is_empty() {
+                    report.problems.push(format!(
+                        "Card {} of deck \"{}\" has no question",
+                        card.id, deck.title
+                    ));
+                }
+                if card.answer.trim().is_empty() {
+                    report.problems.push(format!(
+                        "Card {} of deck \"{}\" has no answer",
+                        card.id, deck.title
+                    ));
+                }
+            }
+        }
+    }
+}
+
+// Hands out the next card id of a deck, if it has any left
+fn take_id(next: &mut HashMap<u32, Option<u32>>, deck_id: u32) -> Option<u32> {
+    let next = next.entry(deck_id).or_insert(Some(0));
+    let id = (*next)?;
+    *next = id.checked_add(1);
+    Some(id)
+}
+
+// The later of two next ids, where None means the ids have run out
+fn later(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    Some(a?.max(b?))
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fmt;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+// Starts every encrypted file, followed by the salt the key was derived with,
+// the nonce and then the encrypted collection
+const MAGIC: &[u8] = b"ranki encrypted 1\n";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Whether some saved bytes are an encrypted collection rather than JSON
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encrypts and decrypts collections with a key derived from a passphrase.
+/// Deriving a key is slow on purpose, so the last one is kept along with the
+/// salt it came from, and reused for saves until a file with another salt is
+/// read.
+#[derive(Clone)]
+pub struct Cipher {
+    passphrase: String,
+    derived: Option<([u8; SALT_LEN], Key)>,
+}
+
+/// An encrypted file couldn't be decrypted, because no passphrase was given or,
+/// as the cipher is authenticated, because it's wrong or the file was tampered
+/// with. Either way it mustn't be treated as a merely unreadable file.
+#[derive(Debug)]
+pub struct WrongPassphrase;
+
+impl fmt::Display for WrongPassphrase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the passphrase is missing or wrong, or the file has been damaged")
+    }
+}
+
+impl Error for WrongPassphrase {}
+
+impl Cipher {
+    pub fn new(passphrase: &str) -> Self {
+        Cipher {
+            passphrase: passphrase.to_string(),
+            derived: None,
+        }
+    }
+
+    pub fn encrypt(&mut self, plain: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let salt = match self.derived {
+            Some((salt, _)) => salt,
+            None => {
+                let mut salt = [0; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+        let key = self.key(&salt)?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let encrypted = ChaCha20Poly1305::new(&key)
+            .encrypt(&nonce, plain)
+            .map_err(|_| "couldn't encrypt the collection")?;
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + encrypted.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&encrypted);
+        Ok(bytes)
+    }
+
+    pub fn decrypt(&mut self, bytes: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        if !is_encrypted(bytes) || bytes.len() < MAGIC.len() + SALT_LEN + NONCE_LEN {
+            return Err("not an encrypted collection".into());
+        }
+        let (salt, rest) = bytes[MAGIC.len()..].split_at(SALT_LEN);
+        let (nonce, encrypted) = rest.split_at(NONCE_LEN);
+        let mut salt_bytes = [0; SALT_LEN];
+        salt_bytes.copy_from_slice(salt);
+        let key = self.key(&salt_bytes)?;
+        ChaCha20Poly1305::new(&key)
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| WrongPassphrase.into())
+    }
+
+    fn key(&mut self, salt: &[u8; SALT_LEN]) -> Result<Key, Box<dyn Error>> {
+        if let Some((derived_salt, key)) = &self.derived {
+            if derived_salt == salt {
+                return Ok(*key);
+            }
+        }
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("couldn't derive a key from the passphrase: {}", e))?;
+        self.derived = Some((*salt, key));
+        Ok(key)
+    }
+}
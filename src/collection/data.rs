@@ -1,41 +1,294 @@
-use std::error::Error;
-use std::fs::{File, OpenOptions};
-use std::io::prelude::*;
-use std::time::SystemTime;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use uuid::Uuid;
 
+use super::backup::{BackupInfo, BackupSettings};
+use super::check::CheckReport;
+use super::index::CardIndex;
+use super::profile::Profile;
 use super::schedule::{schedule, SchedStage};
+use super::schema;
+use super::search::Query;
+use super::trash::Trash;
 
 pub enum SendData {
     Collection(Collection),
-    Quiz(Vec<(u32, u32, String, String)>),
+    Quiz(Vec<QuizCard>),
+    Cram(Vec<QuizCard>),
+    /// Descriptions of the edits undo and redo would act on, if any
+    History(Option<String>, Option<String>),
+    Backups(Vec<BackupInfo>),
+    Profiles(Vec<Profile>),
+    /// Something else changed the stored collection while changes made here
+    /// were still unsaved
+    Conflict,
+    /// What checking the collection found and fixed
+    Check(CheckReport),
+    /// The profile with this id is encrypted, and a passphrase for it is
+    /// needed to open it, after a wrong one if true
+    PassphraseNeeded(u32, bool),
+    /// Something asked for couldn't be done
+    Error(String),
+}
+
+/// A card as it is presented during a quiz
+#[derive(Clone, Debug)]
+pub struct QuizCard {
+    /// Uid of the card, which the answer is sent back for
+    pub uid: Uuid,
+    pub question: String,
+    pub answer: String,
+    pub note: String,
+    pub flag: Option<Flag>,
+    pub marked: bool,
+}
+
+impl QuizCard {
+    fn new(card: &Card) -> Self {
+        QuizCard {
+            uid: card.uid,
+            question: card.question.clone(),
+            answer: card.answer.clone(),
+            note: card.note.clone(),
+            flag: card.flag,
+            marked: card.marked,
+        }
+    }
+}
+
+/// Coloured flags for setting cards aside, with whatever meaning the user
+/// likes to give them (e.g. "needs rewrite" or "check source")
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub enum Flag {
+    Red,
+    Orange,
+    Green,
+    Blue,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 4] = [Flag::Red, Flag::Orange, Flag::Green, Flag::Blue];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flag::Red => "Red",
+            Flag::Orange => "Orange",
+            Flag::Green => "Green",
+            Flag::Blue => "Blue",
+        }
+    }
+}
+
+/// Order in which cards are presented when cramming
+#[derive(Clone, Copy, Debug)]
+pub enum CramOrder {
+    /// Deck by deck, in the order the cards were added
+    Added,
+    Random,
+    /// Most often forgotten first, then shortest interval first
+    Hardest,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Collection {
+    /// Format the collection was saved in, see `schema`
+    version: u32,
     pub id: u32,
     pub owner: String,
-    pub decks: Vec<Deck>,
-    curr_deck_id: u32,
+    /// Add and remove decks through the collection's methods, so that the
+    /// index of their positions stays in step. Decks are shared between
+    /// copies of the collection, such as those kept for undo and sent to the
+    /// GUI, until one of the copies changes them.
+    pub decks: Vec<Arc<Deck>>,
+    pub(super) curr_deck_id: u32,
+    pub trash: Arc<Trash>,
+    pub backup_settings: BackupSettings,
+    /// Deck id to position in `decks`, rebuilt on load
+    #[serde(skip)]
+    deck_positions: HashMap<u32, usize>,
+    /// Deck uid to deck id, rebuilt on load
+    #[serde(skip)]
+    deck_uids: HashMap<Uuid, u32>,
 }
 
-impl Collection {
-    const CONFIG_FILE: &'static str = "anki.conf";
+impl Default for Collection {
+    fn default() -> Self {
+        Collection::new()
+    }
+}
 
+impl Collection {
     pub fn new() -> Self {
         Collection {
+            version: schema::VERSION,
             id: 0,
             owner: "".to_string(),
             decks: Vec::new(),
             curr_deck_id: 0,
+            trash: Arc::default(),
+            backup_settings: BackupSettings::default(),
+            deck_positions: HashMap::new(),
+            deck_uids: HashMap::new(),
         }
     }
 
     pub fn add_deck(&mut self, title: &str) {
         let deck = Deck::new(self.curr_deck_id, title.to_string());
         self.curr_deck_id += 1;
-        self.decks.push(deck);
+        self.push_deck(deck);
+    }
+
+    pub(super) fn push_deck(&mut self, deck: Deck) {
+        self.deck_positions.insert(deck.id, self.decks.len());
+        self.deck_uids.insert(deck.uid, deck.id);
+        self.decks.push(Arc::new(deck));
+    }
+
+    /// The deck at a position, to be changed, copying it first if another
+    /// copy of the collection shares it
+    pub(super) fn deck_mut(&mut self, pos: usize) -> &mut Deck {
+        Arc::make_mut(&mut self.decks[pos])
+    }
+
+    /// The trash, to be changed, copying it first if it's shared
+    pub(super) fn trash_mut(&mut self) -> &mut Trash {
+        Arc::make_mut(&mut self.trash)
+    }
+
+    /// Rebuilds the lookup tables over decks and cards, which aren't saved
+    /// along with the collection
+    pub(super) fn reindex(&mut self) {
+        self.deck_positions = self
+            .decks
+            .iter()
+            .enumerate()
+            .map(|(pos, deck)| (deck.id, pos))
+            .collect();
+        self.deck_uids = self.decks.iter().map(|deck| (deck.uid, deck.id)).collect();
+        for deck in &mut self.decks {
+            Arc::make_mut(deck).reindex();
+        }
+        for trashed in &mut self.trash_mut().decks {
+            trashed.deck.reindex();
+        }
+    }
+
+    /// Creates a filtered deck, borrowing every card from the regular decks
+    /// that matches the query. Returns the id of the new deck.
+    pub fn add_filtered_deck(
+        &mut self,
+        title: &str,
+        query: &str,
+        reschedule: bool,
+    ) -> Result<u32, String> {
+        Query::parse(query)?;
+        let id = self.curr_deck_id;
+        let mut deck = Deck::new(id, title.to_string());
+        deck.filter = Some(Filter {
+            query: query.to_string(),
+            reschedule,
+        });
+        self.curr_deck_id += 1;
+        self.push_deck(deck);
+        self.fill_filtered_deck(id)?;
+        Ok(id)
+    }
+
+    /// Returns the cards of a filtered deck home and borrows a fresh set of
+    /// cards matching its query.
+    pub fn rebuild_filtered_deck(&mut self, id: u32) -> Result<(), String> {
+        // Fail before emptying the deck if it can't be filled again
+        let pos = self.deck_pos_by_id(id);
+        if let Some(filter) = pos.and_then(|pos| self.decks[pos].filter.as_ref()) {
+            Query::parse(&filter.query)?;
+        }
+        self.empty_filtered_deck(id)?;
+        self.fill_filtered_deck(id)
+    }
+
+    /// Returns every card borrowed by a filtered deck to its home deck. Cards
+    /// whose home deck is gone stay behind, as there's nowhere else for them.
+    pub fn empty_filtered_deck(&mut self, id: u32) -> Result<(), String> {
+        let pos = match self.deck_pos_by_id(id) {
+            Some(pos) => pos,
+            None => return Err(format!("could not find deck by id {}", id)),
+        };
+        if self.decks[pos].filter.is_none() {
+            return Err(format!("deck of id {} is not a filtered deck", id));
+        }
+        let cards = self.deck_mut(pos).take_cards(|_| true);
+        let mut homeless = vec![];
+        for card in cards {
+            if let Err(card) = self.return_home(card) {
+                homeless.push(*card);
+            }
+        }
+        if !homeless.is_empty() {
+            println!(
+                "{} cards in filtered deck {} have no home deck to return to",
+                homeless.len(),
+                id
+            );
+            self.deck_mut(pos).extend_cards(homeless);
+        }
+        Ok(())
+    }
+
+    fn fill_filtered_deck(&mut self, id: u32) -> Result<(), String> {
+        let pos = match self.deck_pos_by_id(id) {
+            Some(pos) => pos,
+            None => return Err(format!("could not find deck by id {}", id)),
+        };
+        let query = match &self.decks[pos].filter {
+            Some(filter) => Query::parse(&filter.query)?,
+            None => return Err(format!("deck of id {} is not a filtered deck", id)),
+        };
+        let mut borrowed = vec![];
+        for deck in self.decks.iter_mut().filter(|deck| deck.filter.is_none()) {
+            let matching: HashSet<u32> = deck
+                .cards
+                .iter()
+                .filter(|card| query.matches(deck, card))
+                .map(|card| card.id)
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            let deck = Arc::make_mut(deck);
+            for mut card in deck.take_cards(|card| matching.contains(&card.id)) {
+                card.home = Some((deck.id, card.id));
+                borrowed.push(card);
+            }
+        }
+        let deck = self.deck_mut(pos);
+        for mut card in borrowed {
+            card.id = deck.curr_card_id;
+            deck.curr_card_id += 1;
+            deck.extend_cards(vec![card]);
+        }
+        Ok(())
+    }
+
+    // Puts a borrowed card back into its home deck under its original id,
+    // handing the card back if the home deck no longer exists
+    pub(super) fn return_home(&mut self, mut card: Card) -> Result<(), Box<Card>> {
+        let (deck_id, card_id) = match card.home {
+            Some(home) => home,
+            None => return Err(Box::new(card)),
+        };
+        match self.deck_pos_by_id(deck_id) {
+            Some(pos) => {
+                card.id = card_id;
+                card.home = None;
+                self.deck_mut(pos).insert_card(card);
+                Ok(())
+            }
+            None => Err(Box::new(card)),
+        }
     }
 
     pub fn add_card(
@@ -43,151 +296,389 @@ impl Collection {
         deck_id: u32,
         question: String,
         answer: String,
+        note: String,
     ) -> Result<(), String> {
         if let Some(pos) = self.deck_pos_by_id(deck_id) {
-            self.decks[pos].add_card(question, answer);
+            if self.decks[pos].filter.is_some() {
+                return Err(format!("cannot add cards to filtered deck {}", deck_id));
+            }
+            self.deck_mut(pos).add_card(question, answer, note);
             return Ok(());
         }
-        return Err(format!("could not find deck by id {}", deck_id).to_string());
+        Err(format!("could not find deck by id {}", deck_id))
     }
 
-    pub fn remove_card(&mut self, deck_id: u32, card_id: u32) -> Result<(), String> {
-        if let Some(pos) = self.deck_pos_by_id(deck_id) {
-            self.decks[pos].remove_card_by_id(card_id);
-            return Ok(());
+    /// Takes a card out of whichever deck it's in, returning it along with the
+    /// id of that deck
+    pub(super) fn remove_card(&mut self, uid: Uuid) -> Result<(u32, Card), String> {
+        let (deck_pos, card_pos) = self.locate(uid)?;
+        let deck = self.deck_mut(deck_pos);
+        let card_id = deck.cards[card_pos].id;
+        match deck.remove_card_by_id(card_id) {
+            Some(card) => Ok((deck.id, card)),
+            None => Err(format!("could not find card by id {}", card_id)),
         }
-        return Err(format!("could not find deck by id {}", deck_id).to_string());
     }
 
     pub fn edit_card(
         &mut self,
-        deck_id: u32,
-        card_id: u32,
+        uid: Uuid,
         new_q: String,
         new_a: String,
+        new_note: String,
     ) -> Result<(), String> {
-        if let Some(pos) = self.deck_pos_by_id(deck_id) {
-            match self.decks[pos].edit_card_by_id(card_id, new_q, new_a) {
-                Ok(_) => {
-                    return Ok(());
-                }
-                Err(e) => {
-                    return Err(e);
-                }
+        let (deck_pos, card_pos) = self.locate(uid)?;
+        let deck = self.deck_mut(deck_pos);
+        let card_id = deck.cards[card_pos].id;
+        deck.edit_card_by_id(card_id, new_q, new_a, new_note)
+    }
+
+    pub fn rename_deck(&mut self, id: u32, title: String) -> Result<(), String> {
+        match self.deck_pos_by_id(id) {
+            Some(pos) => {
+                let deck = self.deck_mut(pos);
+                deck.title = title;
+                deck.modified = Some(SystemTime::now());
+                Ok(())
             }
+            None => Err(format!("could not find deck by id {}", id)),
         }
-        Err(format!("could not find deck by id {}", deck_id))
     }
 
     pub fn remove_deck_by_id(&mut self, id: u32) -> Option<Deck> {
-        if let Some(pos) = self.decks.iter().position(|deck| deck.id == id) {
-            Some(self.decks.remove(pos))
+        let is_filtered = self.decks.iter().any(|deck| deck.id == id && deck.filter.is_some());
+        if is_filtered {
+            // Filtered decks only borrow their cards, so hand them back first
+            if let Err(e) = self.empty_filtered_deck(id) {
+                println!("error returning cards from deleted filtered deck: {}", e);
+            }
         } else {
-            None
+            // Bring home any cards lent out, so they're removed with the deck
+            let mut lent = vec![];
+            let is_lent = |card: &Card| card.home.is_some_and(|(deck_id, _)| deck_id == id);
+            for deck in self.decks.iter_mut().filter(|deck| deck.filter.is_some()) {
+                if deck.cards.iter().any(|card| is_lent(card)) {
+                    lent.extend(Arc::make_mut(deck).take_cards(is_lent));
+                }
+            }
+            for card in lent {
+                let _ = self.return_home(card);
+            }
+        }
+        let pos = self.deck_pos_by_id(id)?;
+        let deck = self.decks.remove(pos);
+        self.deck_positions.remove(&id);
+        self.deck_uids.remove(&deck.uid);
+        for (pos, deck) in self.decks.iter().enumerate().skip(pos) {
+            self.deck_positions.insert(deck.id, pos);
         }
+        Some(Arc::unwrap_or_clone(deck))
     }
 
     pub fn deck_pos_by_id(&self, id: u32) -> Option<usize> {
-        if let Some(pos) = self.decks.iter().position(|deck| deck.id == id) {
-            Some(pos)
-        } else {
-            None
+        self.deck_positions.get(&id).copied()
+    }
+
+    pub fn deck_by_uid(&self, uid: Uuid) -> Option<&Deck> {
+        let pos = self.deck_pos_by_id(*self.deck_uids.get(&uid)?)?;
+        Some(&self.decks[pos])
+    }
+
+    /// Finds a card by its uid, or the uid of its note, along with the deck
+    /// it's in, wherever that is
+    pub fn card_by_uid(&self, uid: Uuid) -> Option<(&Deck, &Card)> {
+        let (deck_pos, card_pos) = self.locate(uid).ok()?;
+        let deck = &self.decks[deck_pos];
+        Some((&**deck, &deck.cards[card_pos]))
+    }
+
+    // Positions of the deck holding the card with the given uid, or whose
+    // note has it, and of the card within it. Each deck's index is asked in
+    // turn, so it costs a lookup per deck rather than per card.
+    pub(super) fn locate(&self, uid: Uuid) -> Result<(usize, usize), String> {
+        self.decks
+            .iter()
+            .enumerate()
+            .find_map(|(pos, deck)| deck.index.position_of_uid(uid).map(|card| (pos, card)))
+            .ok_or_else(|| format!("could not find card by uid {}", uid))
+    }
+
+    pub fn set_tags(&mut self, uid: Uuid, tags: Vec<String>) -> Result<(), String> {
+        let card = self.card_mut(uid)?;
+        card.tags = tags;
+        card.modified = Some(SystemTime::now());
+        Ok(())
+    }
+
+    /// Puts a card's content back to how it was before one of its edits. The
+    /// revert is itself recorded as an edit, so it can be reverted in turn.
+    pub fn revert_card(&mut self, uid: Uuid, revision: usize) -> Result<(), String> {
+        let old = match self.card_by_uid(uid) {
+            Some((_, card)) => match card.revisions.get(revision) {
+                Some(old) => old.clone(),
+                None => return Err(format!("card {} has no revision {}", uid, revision)),
+            },
+            None => return Err(format!("could not find card by uid {}", uid)),
+        };
+        self.edit_card(uid, old.question, old.answer, old.note)
+    }
+
+    pub fn set_flag(&mut self, uid: Uuid, flag: Option<Flag>) -> Result<(), String> {
+        self.card_mut(uid)?.flag = flag;
+        Ok(())
+    }
+
+    pub fn set_marked(&mut self, uid: Uuid, marked: bool) -> Result<(), String> {
+        self.card_mut(uid)?.marked = marked;
+        Ok(())
+    }
+
+    fn card_mut(&mut self, uid: Uuid) -> Result<&mut Card, String> {
+        let (deck_pos, card_pos) = self.locate(uid)?;
+        Ok(Arc::make_mut(&mut self.deck_mut(deck_pos).cards[card_pos]))
+    }
+
+    /// Returns every card in the collection matching the given query, paired
+    /// with the deck it belongs to, in collection order.
+    pub fn search(&self, query: &Query) -> Vec<(&Deck, &Card)> {
+        let mut found = vec![];
+        for deck in &self.decks {
+            for card in &deck.cards {
+                if query.matches(deck, card) {
+                    found.push((&**deck, &**card));
+                }
+            }
         }
+        found
+    }
+
+    /// Ids of the given decks along with those of all their children. A deck
+    /// is the child of another when its title starts with the parent's title
+    /// followed by "::", so "Languages::Spanish" is a child of "Languages".
+    pub fn with_children(&self, deck_ids: &[u32]) -> Vec<u32> {
+        let prefixes: Vec<String> = self
+            .decks
+            .iter()
+            .filter(|deck| deck_ids.contains(&deck.id))
+            .map(|deck| format!("{}::", deck.title))
+            .collect();
+        self.decks
+            .iter()
+            .filter(|deck| {
+                deck_ids.contains(&deck.id)
+                    || prefixes.iter().any(|prefix| deck.title.starts_with(prefix))
+            })
+            .map(|deck| deck.id)
+            .collect()
     }
 
-    pub(super) fn get_quiz(&self) -> Vec<(u32, u32, String, String)> {
+    /// Number of cards up for review in a deck and its children
+    pub fn due_count(&self, deck_id: u32) -> usize {
+        self.with_children(&[deck_id])
+            .into_iter()
+            .filter_map(|id| self.deck_pos_by_id(id))
+            .map(|pos| self.decks[pos].due_count())
+            .sum()
+    }
+
+    /// Gathers the cards up for review, either from the whole collection or
+    /// only from the given decks and their children.
+    pub fn get_quiz(&self, deck_ids: Option<&[u32]>) -> Vec<QuizCard> {
+        let scope = deck_ids.map(|ids| self.with_children(ids));
         let mut quiz = vec![];
         for deck in &self.decks {
-            let card_quiz = deck.get_quiz();
-            for (card_id, question, answer) in card_quiz {
-                quiz.push((deck.id, card_id, question, answer));
+            if let Some(scope) = &scope {
+                if !scope.contains(&deck.id) {
+                    continue;
+                }
             }
+            quiz.extend(deck.get_quiz());
         }
         quiz
     }
 
-    pub(super) fn process_results(
-        &mut self,
-        results: Vec<(u32, u32, Option<bool>)>,
-    ) -> Result<(), String> {
-        for result in results {
-            if let Some(passed) = result.2 {
-                match self.deck_pos_by_id(result.0) {
-                    Some(pos) => {
-                        self.decks[pos].process_result(result.1, passed)?;
-                    }
-                    None => {
-                        return Err(format!("error: could not find deck by id: {}", result.1));
-                    }
-                }
-            }
+    /// Gathers every card in the given decks regardless of whether it is due,
+    /// for a run through the material that leaves scheduling alone.
+    pub fn get_cram(
+        &self,
+        deck_ids: &[u32],
+        order: CramOrder,
+    ) -> Vec<QuizCard> {
+        let mut cards = vec![];
+        for deck in self.decks.iter().filter(|deck| deck_ids.contains(&deck.id)) {
+            cards.extend(deck.cards.iter().map(|card| &**card));
         }
-        Ok(())
+        match order {
+            CramOrder::Added => {}
+            CramOrder::Random => shuffle(&mut cards),
+            CramOrder::Hardest => cards.sort_by(|a, b| {
+                b.lapses
+                    .cmp(&a.lapses)
+                    .then(a.stage.duration().cmp(&b.stage.duration()))
+            }),
+        }
+        cards.into_iter().map(QuizCard::new).collect()
     }
 
-    pub fn load_from_file() -> Result<Self, Box<dyn Error>> {
-        let mut config_file = match File::open(Collection::CONFIG_FILE) {
-            Ok(file) => file,
-            Err(error) => {
-                match error.kind() {
-                    std::io::ErrorKind::NotFound => {
-                        println!("No collection configuration file found. Creating new file.");
-                    }
-                    _ => {
-                        return Err(Box::new(error));
-                    }
-                }
-                return Ok(Collection::new());
+    /// Reschedules the cards answered during a quiz, given by uid along with
+    /// whether they were recalled, if they were answered at all. Every card is
+    /// found before any is rescheduled, so a failure reschedules none.
+    pub fn process_results(&mut self, results: Vec<(Uuid, Option<bool>)>) -> Result<(), String> {
+        let mut answered = vec![];
+        for (uid, passed) in results {
+            if let Some(passed) = passed {
+                answered.push((self.locate(uid)?, passed));
             }
-        };
-
-        let mut buffer = String::new();
-        config_file.read_to_string(&mut buffer)?;
-        let collection = serde_json::from_str(&buffer)?;
-
-        Ok(collection)
+        }
+        for ((deck_pos, card_pos), passed) in answered {
+            // Filtered decks may be studied without rescheduling
+            if let Some(Filter {
+                reschedule: false, ..
+            }) = self.decks[deck_pos].filter
+            {
+                continue;
+            }
+            let deck = self.deck_mut(deck_pos);
+            let card_id = deck.cards[card_pos].id;
+            deck.process_result(card_id, passed)?;
+        }
+        Ok(())
     }
 
-    pub fn flush_to_file(&self) -> Result<(), Box<dyn Error>> {
-        let mut file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(Collection::CONFIG_FILE)?;
-        let bytes = serde_json::to_vec(&self)?;
-        file.write_all(&bytes)?;
-        Ok(())
+    /// Everything about the collection besides its decks, for storage that
+    /// keeps decks and cards apart. Keep in step with the collection's fields.
+    pub(super) fn header(&self) -> Value {
+        json!({
+            "version": self.version,
+            "id": self.id,
+            "owner": self.owner,
+            "curr_deck_id": self.curr_deck_id,
+            "trash": self.trash,
+            "backup_settings": self.backup_settings,
+        })
     }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Deck {
     pub id: u32,
+    /// Identifies the deck across collections and machines
+    pub uid: Uuid,
     pub title: String,
-    pub cards: Vec<Card>,
-    curr_card_id: u32,
+    /// Add and remove cards through the deck's methods, so that its index
+    /// stays in step. Cards are shared between copies of the deck, so editing
+    /// one card of a large deck doesn't copy all the others.
+    pub cards: Vec<Arc<Card>>,
+    pub(super) curr_card_id: u32,
+    pub filter: Option<Filter>,
+    /// Shared with copies of the deck, like its cards, until either changes
+    #[serde(skip)]
+    index: Arc<CardIndex>,
+    /// Unknown for decks from before timestamps were kept
+    created: Option<SystemTime>,
+    modified: Option<SystemTime>,
+}
+
+/// Marks a deck as filtered, holding cards borrowed from other decks that
+/// matched the search query at the time the deck was built
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Filter {
+    pub query: String,
+    /// Whether answers given while studying the deck affect scheduling
+    pub reschedule: bool,
 }
 
 impl Deck {
     fn new(id: u32, title: String) -> Self {
         Deck {
             id,
+            uid: Uuid::new_v4(),
             title,
             cards: Vec::new(),
             curr_card_id: 0,
+            filter: None,
+            index: Arc::default(),
+            created: Some(SystemTime::now()),
+            modified: Some(SystemTime::now()),
         }
     }
 
-    pub fn add_card(&mut self, question: String, answer: String) {
-        self.cards
-            .push(Card::new(self.curr_card_id, question, answer));
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    /// When the deck was last renamed or had cards added, edited or removed
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    pub(super) fn reindex(&mut self) {
+        self.index = Arc::new(CardIndex::build(&self.cards));
+    }
+
+    /// Everything about the deck besides its cards, for storage that keeps
+    /// decks and cards apart. Keep in step with the deck's fields.
+    pub(super) fn header(&self) -> Value {
+        json!({
+            "id": self.id,
+            "uid": self.uid,
+            "title": self.title,
+            "curr_card_id": self.curr_card_id,
+            "filter": self.filter,
+            "created": self.created,
+            "modified": self.modified,
+        })
+    }
+
+    pub fn card(&self, card_id: u32) -> Option<&Card> {
+        self.index.position(card_id).map(|pos| &*self.cards[pos])
+    }
+
+    pub fn add_card(&mut self, question: String, answer: String, note: String) {
+        let card = Card::new(self.curr_card_id, question, answer, note);
         self.curr_card_id += 1;
+        self.modified = Some(SystemTime::now());
+        self.extend_cards(vec![card]);
+    }
+
+    // Appends cards that already have ids in this deck
+    fn extend_cards(&mut self, cards: Vec<Card>) {
+        for card in cards {
+            Arc::make_mut(&mut self.index).push(self.cards.len(), &card);
+            self.cards.push(Arc::new(card));
+        }
+    }
+
+    // Takes every card out of the deck for which the predicate holds
+    fn take_cards<F: FnMut(&Card) -> bool>(&mut self, mut take: F) -> Vec<Card> {
+        let (taken, kept): (Vec<_>, _) = self.cards.drain(..).partition(|card| take(card));
+        self.cards = kept;
+        self.reindex();
+        taken.into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// Puts a card that was previously taken out of the deck back in its
+    /// original place, going by its id
+    pub(super) fn insert_card(&mut self, card: Card) {
+        let idx = self
+            .cards
+            .iter()
+            .position(|c| c.id > card.id)
+            .unwrap_or(self.cards.len());
+        if idx == self.cards.len() {
+            self.extend_cards(vec![card]);
+        } else {
+            self.cards.insert(idx, Arc::new(card));
+            self.reindex();
+        }
     }
 
     pub fn remove_card_by_id(&mut self, card_id: u32) -> Option<Card> {
         if let Some(pos) = self.card_pos_by_id(card_id) {
-            Some(self.cards.remove(pos))
+            self.modified = Some(SystemTime::now());
+            let card = self.cards.remove(pos);
+            self.reindex();
+            Some(Arc::unwrap_or_clone(card))
         } else {
             None
         }
@@ -198,38 +689,61 @@ impl Deck {
         card_id: u32,
         new_q: String,
         new_a: String,
+        new_note: String,
     ) -> Result<(), String> {
         if let Some(pos) = self.card_pos_by_id(card_id) {
-            self.cards[pos].question = new_q;
-            self.cards[pos].answer = new_a;
-            return Ok(());
+            if Arc::make_mut(&mut self.cards[pos]).edit(new_q, new_a, new_note) {
+                self.modified = Some(SystemTime::now());
+            }
+            Ok(())
         } else {
             Err(format!("could not find card by id {}", card_id))
         }
     }
 
-    fn card_pos_by_id(&mut self, card_id: u32) -> Option<usize> {
-        if let Some(pos) = self.cards.iter().position(|card| card.id == card_id) {
-            Some(pos)
-        } else {
-            None
+    fn card_pos_by_id(&self, card_id: u32) -> Option<usize> {
+        self.index.position(card_id)
+    }
+
+    // Cramming a filtered deck goes through every card, due or not
+    fn is_cram(&self) -> bool {
+        match &self.filter {
+            Some(filter) => !filter.reschedule,
+            None => false,
         }
     }
 
-    fn get_quiz(&self) -> Vec<(u32, String, String)> {
-        let mut quiz = vec![];
-        for card in &self.cards {
-            if card.needs_quiz() {
-                quiz.push((card.id, card.question.clone(), card.answer.clone()));
-            }
+    fn get_quiz(&self) -> Vec<QuizCard> {
+        if self.is_cram() {
+            return self
+                .cards
+                .iter()
+                .map(|card| QuizCard::new(card))
+                .collect();
+        }
+        self.index
+            .due_before(SystemTime::now())
+            .filter_map(|card_id| self.card(card_id))
+            .map(QuizCard::new)
+            .collect()
+    }
+
+    fn due_count(&self) -> usize {
+        if self.is_cram() {
+            self.cards.len()
+        } else {
+            self.index.due_before(SystemTime::now()).count()
         }
-        quiz
     }
 
     fn process_result(&mut self, card_id: u32, passed: bool) -> Result<(), String> {
         match self.card_pos_by_id(card_id) {
             Some(pos) => {
-                self.cards[pos].process_result(passed);
+                let card = Arc::make_mut(&mut self.cards[pos]);
+                let index = Arc::make_mut(&mut self.index);
+                index.remove_due(card);
+                card.process_result(passed);
+                index.insert_due(card);
             }
             None => {
                 return Err(format!("could not find card by id: {}", card_id));
@@ -242,35 +756,166 @@ impl Deck {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Card {
     pub id: u32,
+    /// Identifies the card across collections and machines. Unlike `id` it
+    /// stays the same when a filtered deck borrows the card, so it's what
+    /// everything outside of the card's deck refers to the card by.
+    pub uid: Uuid,
     pub question: String,
     pub answer: String,
     pub note: String,
+    /// Identifies the card's note on its own, as `uid` does the card
+    pub note_uid: Uuid,
+    pub tags: Vec<String>,
+    pub flag: Option<Flag>,
+    pub marked: bool,
     checked: SystemTime,
     stage: SchedStage,
+    lapses: u32,
+    failed: Option<SystemTime>,
+    /// (deck id, card id) the card had before a filtered deck borrowed it
+    pub(super) home: Option<(u32, u32)>,
+    /// Unknown for cards from before timestamps were kept
+    created: Option<SystemTime>,
+    modified: Option<SystemTime>,
+    /// Content the card had before each of its edits, oldest first
+    revisions: Vec<Revision>,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Revision {
+    pub question: String,
+    pub answer: String,
+    pub note: String,
+    /// When this content was replaced by an edit
+    pub replaced: SystemTime,
 }
 
 impl Card {
-    fn new(id: u32, question: String, answer: String) -> Self {
+    fn new(id: u32, question: String, answer: String, note: String) -> Self {
         Card {
             id,
+            uid: Uuid::new_v4(),
             question,
             answer,
-            note: "".to_string(),
+            note,
+            note_uid: Uuid::new_v4(),
+            tags: Vec::new(),
+            flag: None,
+            marked: false,
             checked: SystemTime::now(),
             stage: SchedStage::New,
+            lapses: 0,
+            failed: None,
+            home: None,
+            created: Some(SystemTime::now()),
+            modified: Some(SystemTime::now()),
+            revisions: Vec::new(),
         }
     }
 
-    fn needs_quiz(&self) -> bool {
-        if (self.checked + self.stage.duration()) < SystemTime::now() {
-            true
-        } else {
-            false
+    pub fn created(&self) -> Option<SystemTime> {
+        self.created
+    }
+
+    /// When the card's content or tags were last changed
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    // Replaces the card's content, keeping the old content as a revision.
+    // Returns false if nothing actually changed.
+    fn edit(&mut self, question: String, answer: String, note: String) -> bool {
+        if question == self.question && answer == self.answer && note == self.note {
+            return false;
         }
+        let now = SystemTime::now();
+        self.revisions.push(Revision {
+            question: std::mem::replace(&mut self.question, question),
+            answer: std::mem::replace(&mut self.answer, answer),
+            note: std::mem::replace(&mut self.note, note),
+            replaced: now,
+        });
+        self.modified = Some(now);
+        true
+    }
+
+    /// Human readable name of the scheduling stage the card is in
+    pub fn stage_name(&self) -> &'static str {
+        self.stage.name()
+    }
+
+    pub(super) fn stage(&self) -> &SchedStage {
+        &self.stage
+    }
+
+    /// Time between the last check of the card and its next review
+    pub fn interval(&self) -> Duration {
+        self.stage.duration()
+    }
+
+    /// Point in time at which the card is next up for review
+    pub fn due(&self) -> SystemTime {
+        self.checked + self.stage.duration()
+    }
+
+    /// Number of times the card has been forgotten after graduating to review
+    pub fn lapses(&self) -> u32 {
+        self.lapses
+    }
+
+    /// When the card was last answered during a quiz, or when it was added if
+    /// it never has been
+    pub fn last_reviewed(&self) -> SystemTime {
+        self.checked
+    }
+
+    /// When the card was last forgotten during a quiz, if ever
+    pub fn last_failed(&self) -> Option<SystemTime> {
+        self.failed
+    }
+
+    /// Id of the deck a borrowed card will return to once its filtered deck
+    /// is emptied
+    pub fn home_deck(&self) -> Option<u32> {
+        self.home.map(|(deck_id, _)| deck_id)
+    }
+
+    /// Turns a borrowed card back into the card of its home deck, returning
+    /// the id of that deck
+    pub(super) fn leave_filtered_deck(&mut self) -> Option<u32> {
+        let (deck_id, card_id) = self.home.take()?;
+        self.id = card_id;
+        Some(deck_id)
     }
 
     fn process_result(&mut self, passed: bool) {
+        if let (SchedStage::Reviewing(_), false) = (&self.stage, passed) {
+            self.lapses += 1;
+        }
+        if !passed {
+            self.failed = Some(SystemTime::now());
+        }
         self.stage = schedule(&self.stage, passed);
         self.checked = SystemTime::now();
     }
 }
+
+// Fisher-Yates shuffle driven by a xorshift generator seeded from the clock,
+// which is plenty random enough for ordering flashcards
+fn shuffle<T>(items: &mut [T]) {
+    let mut state = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        items.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
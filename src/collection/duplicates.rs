@@ -0,0 +1,94 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use super::data::{Card, Collection};
+
+/// Reduces a question to the form used for comparing cards, ignoring case,
+/// surrounding whitespace and runs of whitespace within it
+pub fn normalise(question: &str) -> String {
+    question
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl Collection {
+    /// Finds the uids of the cards whose question matches the given one once
+    /// normalised
+    pub fn find_question(&self, question: &str) -> Vec<Uuid> {
+        let question = normalise(question);
+        let mut found = vec![];
+        for deck in &self.decks {
+            for card in &deck.cards {
+                if normalise(&card.question) == question {
+                    found.push(card.uid);
+                }
+            }
+        }
+        found
+    }
+
+    /// Groups of two or more cards across the collection sharing a normalised
+    /// question, as uids in collection order
+    pub fn find_duplicates(&self) -> Vec<Vec<Uuid>> {
+        let mut order = vec![];
+        let mut groups: HashMap<String, Vec<Uuid>> = HashMap::new();
+        for deck in &self.decks {
+            for card in &deck.cards {
+                let key = normalise(&card.question);
+                if !groups.contains_key(&key) {
+                    order.push(key.clone());
+                }
+                groups.entry(key).or_default().push(card.uid);
+            }
+        }
+        order
+            .into_iter()
+            .filter_map(|key| groups.remove(&key))
+            .filter(|group| group.len() > 1)
+            .collect()
+    }
+
+    /// Merges the given cards into whichever of them has the best review
+    /// history, folding the tags and notes of the others into it before they
+    /// are removed. Every card is found before any is changed, so a missing
+    /// one leaves the collection as it was.
+    pub fn merge_cards(&mut self, cards: &[Uuid]) -> Result<(), String> {
+        let mut seen = HashSet::new();
+        let mut found: Vec<Card> = vec![];
+        for &uid in cards.iter().filter(|uid| seen.insert(**uid)) {
+            match self.card_by_uid(uid) {
+                Some((_, card)) => found.push(card.clone()),
+                None => return Err(format!("could not find card by uid {}", uid)),
+            }
+        }
+        found.sort_by_key(|card| {
+            let (rank, interval) = card.stage().progress();
+            Reverse((rank, interval, Reverse(card.lapses())))
+        });
+        let mut iter = found.into_iter();
+        let mut kept = match iter.next() {
+            Some(best) => best,
+            None => return Ok(()),
+        };
+        for card in iter {
+            for tag in card.tags {
+                if !kept.tags.contains(&tag) {
+                    kept.tags.push(tag);
+                }
+            }
+            if !card.note.is_empty() && !kept.note.contains(&card.note) {
+                if !kept.note.is_empty() {
+                    kept.note.push('\n');
+                }
+                kept.note.push_str(&card.note);
+            }
+            self.remove_card(card.uid)?;
+        }
+        self.set_tags(kept.uid, kept.tags)?;
+        self.edit_card(kept.uid, kept.question, kept.answer, kept.note)
+    }
+}
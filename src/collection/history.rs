@@ -0,0 +1,69 @@
+use super::data::Collection;
+
+/// Undo and redo stacks of collection edits. Each entry holds the state of
+/// the collection from before (for undo) or after (for redo) the edit, which
+/// keeps every kind of action reversible without needing an inverse for each.
+/// Snapshots share every deck the edit left alone, so each costs about as
+/// much as the decks it changed.
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+struct Entry {
+    label: &'static str,
+    collection: Collection,
+}
+
+impl History {
+    /// The number of edits that can be undone before the oldest are dropped
+    const LIMIT: usize = 50;
+
+    pub fn new() -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Records an edit given the collection from before it was made. Making a
+    /// new edit discards anything that had been undone.
+    pub fn record(&mut self, label: &'static str, before: Collection) {
+        self.undo.push(Entry {
+            label,
+            collection: before,
+        });
+        if self.undo.len() > History::LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    /// Steps back one edit, returning the collection to restore
+    pub fn undo(&mut self, current: Collection) -> Option<Collection> {
+        let entry = self.undo.pop()?;
+        self.redo.push(Entry {
+            label: entry.label,
+            collection: current,
+        });
+        Some(entry.collection)
+    }
+
+    /// Reapplies the last undone edit, returning the collection to restore
+    pub fn redo(&mut self, current: Collection) -> Option<Collection> {
+        let entry = self.redo.pop()?;
+        self.undo.push(Entry {
+            label: entry.label,
+            collection: current,
+        });
+        Some(entry.collection)
+    }
+
+    /// Describes the edits that undo and redo would currently act on
+    pub fn labels(&self) -> (Option<String>, Option<String>) {
+        (
+            self.undo.last().map(|entry| entry.label.to_string()),
+            self.redo.last().map(|entry| entry.label.to_string()),
+        )
+    }
+}
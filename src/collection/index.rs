@@ -0,0 +1,63 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use uuid::Uuid;
+
+use super::data::Card;
+
+/// Lookup tables over the cards of a deck, kept in step with its list of
+/// cards so that finding a card by id or uid, or the cards that are due,
+/// doesn't mean walking the whole deck. They aren't saved, so are rebuilt on
+/// load.
+#[derive(Clone, Debug, Default)]
+pub struct CardIndex {
+    /// Card id to position in the deck's list of cards
+    positions: HashMap<u32, usize>,
+    /// Uid of the card, and of its note, to position in the deck's list
+    uids: HashMap<Uuid, usize>,
+    /// (due time, card id) of every card in the deck, soonest first
+    due: BTreeSet<(SystemTime, u32)>,
+}
+
+impl CardIndex {
+    pub fn build(cards: &[Arc<Card>]) -> Self {
+        let mut index = CardIndex::default();
+        for (pos, card) in cards.iter().enumerate() {
+            index.push(pos, card);
+        }
+        index
+    }
+
+    pub fn position(&self, card_id: u32) -> Option<usize> {
+        self.positions.get(&card_id).copied()
+    }
+
+    /// Position of the card with the given uid, or whose note has it
+    pub fn position_of_uid(&self, uid: Uuid) -> Option<usize> {
+        self.uids.get(&uid).copied()
+    }
+
+    /// Records a card appended to the end of the deck at the given position
+    pub fn push(&mut self, pos: usize, card: &Card) {
+        self.positions.insert(card.id, pos);
+        self.uids.insert(card.uid, pos);
+        self.uids.insert(card.note_uid, pos);
+        self.due.insert((card.due(), card.id));
+    }
+
+    // The due time is part of the key, so rescheduling a card means taking it
+    // out before the change and putting it back in after
+    pub fn remove_due(&mut self, card: &Card) {
+        self.due.remove(&(card.due(), card.id));
+    }
+
+    pub fn insert_due(&mut self, card: &Card) {
+        self.due.insert((card.due(), card.id));
+    }
+
+    /// Ids of the cards due before the given time, most overdue first
+    pub fn due_before(&self, time: SystemTime) -> impl Iterator<Item = u32> + '_ {
+        self.due.range(..(time, 0)).map(|(_, card_id)| *card_id)
+    }
+}
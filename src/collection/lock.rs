@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{prelude::*, SeekFrom};
+use std::path::Path;
+use std::process;
+
+use fs2::FileExt;
+
+/// An exclusive lock on a data directory, so that a second Ranki can't open the
+/// same collections and overwrite the first one's saves with its own. The lock
+/// is the operating system's, so it goes away with the process holding it,
+/// even after a crash. The file only says which process that was.
+pub struct Lock {
+    // Held open for as long as the lock is needed
+    _file: File,
+}
+
+impl Lock {
+    pub const FILE: &'static str = "ranki.lock";
+
+    pub fn acquire(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(Lock::FILE);
+        // Opened without truncating, as the holder's id is needed if it's taken
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        if let Err(e) = file.try_lock_exclusive() {
+            if e.kind() != fs2::lock_contended_error().kind() {
+                return Err(e.into());
+            }
+            let mut holder = String::new();
+            file.read_to_string(&mut holder)?;
+            let holder = match holder.trim() {
+                "" => "another process".to_string(),
+                pid => format!("process {}", pid),
+            };
+            return Err(format!(
+                "The collection in {} is already open in another Ranki ({}). \
+                 Close that one first, so the two don't overwrite each other.",
+                dir.display(),
+                holder
+            )
+            .into());
+        }
+
+        // Whatever was left by a process that has since died is out of date
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", process::id())?;
+        Ok(Lock { _file: file })
+    }
+}
@@ -1,6 +1,27 @@
+mod backup;
+mod check;
+mod crypt;
 mod data;
+mod duplicates;
+mod history;
+mod index;
+mod lock;
+mod profile;
+mod replace;
 mod schedule;
+mod search;
+mod sqlite;
+pub mod schema;
+pub mod storage;
+mod trash;
 pub mod store;
 
-pub use data::{Collection, Deck, SendData};
+pub use backup::{BackupInfo, BackupSettings, Backups};
+pub use check::CheckReport;
+pub use crypt::WrongPassphrase;
+pub use data::{Card, Collection, CramOrder, Deck, Flag, QuizCard, Revision, SendData};
+pub use lock::Lock;
+pub use profile::{Profile, Profiles};
+pub use replace::{Replace, ReplacePreview};
+pub use search::Query;
 pub use store::{Action, CollectionService};
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::storage;
+
+/// A named collection, e.g. one per person or per project
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Profile {
+    pub id: u32,
+    pub name: String,
+}
+
+/// The profiles in the data directory and which was opened last, kept in
+/// `profiles.json`. Each profile's collection and backups live in a directory
+/// of their own named after its id, so renaming one doesn't move any files.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Profiles {
+    #[serde(skip)]
+    dir: PathBuf,
+    next_id: u32,
+    last: Option<u32>,
+    list: Vec<Profile>,
+}
+
+impl Profiles {
+    pub const FILE: &'static str = "profiles.json";
+    pub const DIR: &'static str = "profiles";
+    const DEFAULT_NAME: &'static str = "Default";
+
+    /// Reads the profiles in the data directory. The first time, a default
+    /// profile is made. Any collection in the directory itself, from before
+    /// there were profiles, is taken over by the profile opened last.
+    pub fn open(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = dir.join(Profiles::FILE);
+        if path.exists() {
+            let mut buffer = String::new();
+            File::open(&path)?.read_to_string(&mut buffer)?;
+            let mut profiles: Profiles = serde_json::from_str(&buffer)?;
+            profiles.dir = dir.to_path_buf();
+            profiles.adopt(dir)?;
+            return Ok(profiles);
+        }
+
+        let mut profiles = Profiles {
+            dir: dir.to_path_buf(),
+            next_id: 0,
+            last: None,
+            list: vec![],
+        };
+        let id = profiles.create(Profiles::DEFAULT_NAME)?;
+        profiles.last = Some(id);
+        profiles.save()?;
+        profiles.adopt(dir)?;
+        Ok(profiles)
+    }
+
+    /// Moves a collection kept outside of any profile, as older versions did,
+    /// into the profile opened last, which is the one it would have been opened
+    /// as. It's left where it is if that profile has a collection already.
+    pub fn adopt(&self, from: &Path) -> Result<(), Box<dyn Error>> {
+        match self.last().or_else(|| self.list.first().map(|profile| profile.id)) {
+            Some(id) => storage::move_collection(from, &self.dir_of(id)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn list(&self) -> &[Profile] {
+        &self.list
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Profile> {
+        self.list.iter().find(|profile| profile.id == id)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&Profile> {
+        self.list.iter().find(|profile| profile.name == name)
+    }
+
+    /// The profile opened last, if it still exists
+    pub fn last(&self) -> Option<u32> {
+        self.last.filter(|id| self.get(*id).is_some())
+    }
+
+    /// Where the collection of a profile is kept
+    pub fn dir_of(&self, id: u32) -> PathBuf {
+        self.dir.join(Profiles::DIR).join(id.to_string())
+    }
+
+    pub fn set_last(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        self.last = Some(id);
+        self.save()
+    }
+
+    /// Adds an empty profile, returning its id
+    pub fn create(&mut self, name: &str) -> Result<u32, Box<dyn Error>> {
+        let name = self.check_name(name)?;
+        let id = self.next_id;
+        fs::create_dir_all(self.dir_of(id))?;
+        self.next_id += 1;
+        self.list.push(Profile { id, name });
+        self.save()?;
+        Ok(id)
+    }
+
+    pub fn rename(&mut self, id: u32, name: &str) -> Result<(), Box<dyn Error>> {
+        let name = self.check_name(name)?;
+        match self.list.iter_mut().find(|profile| profile.id == id) {
+            Some(profile) => profile.name = name,
+            None => return Err(format!("no profile with id {}", id).into()),
+        }
+        self.save()
+    }
+
+    /// Removes a profile along with its collection and backups, for good
+    pub fn delete(&mut self, id: u32) -> Result<(), Box<dyn Error>> {
+        let pos = match self.list.iter().position(|profile| profile.id == id) {
+            Some(pos) => pos,
+            None => return Err(format!("no profile with id {}", id).into()),
+        };
+        if self.list.len() == 1 {
+            return Err("the only profile can't be deleted".into());
+        }
+        self.list.remove(pos);
+        self.save()?;
+        let dir = self.dir_of(id);
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    fn check_name(&self, name: &str) -> Result<String, Box<dyn Error>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("profile names can't be empty".into());
+        }
+        if self.by_name(name).is_some() {
+            return Err(format!("there is already a profile called {}", name).into());
+        }
+        Ok(name.to_string())
+    }
+
+    // Written the same way as the JSON collection, so a crash mid-save keeps
+    // the previous list
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(Profiles::FILE);
+        let temp = storage::with_suffix(&path, ".tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp)?;
+        file.write_all(&serde_json::to_vec_pretty(self)?)?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)?;
+        storage::sync_parent(&path);
+        Ok(())
+    }
+}
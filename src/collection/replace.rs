@@ -0,0 +1,109 @@
+use regex::Regex;
+use uuid::Uuid;
+
+use super::data::{Card, Collection};
+use super::search::Query;
+
+/// A find-and-replace over the text of the cards matching a search query,
+/// which can scope it to a single deck with `deck:<title>`
+#[derive(Clone, Debug)]
+pub struct Replace {
+    pub find: String,
+    pub replacement: String,
+    /// Treat `find` as a regular expression, allowing `$1` style references
+    /// to capture groups in the replacement
+    pub regex: bool,
+    pub query: String,
+    pub questions: bool,
+    pub answers: bool,
+    pub notes: bool,
+}
+
+/// The text of a card before and after a find-and-replace
+#[derive(Clone, Debug)]
+pub struct ReplacePreview {
+    pub uid: Uuid,
+    pub before: (String, String, String),
+    pub after: (String, String, String),
+}
+
+enum Matcher {
+    Plain(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn replace(&self, text: &str, replacement: &str) -> String {
+        match self {
+            Matcher::Plain(find) => text.replace(find.as_str(), replacement),
+            Matcher::Regex(re) => re.replace_all(text, replacement).into_owned(),
+        }
+    }
+}
+
+impl Replace {
+    fn matcher(&self) -> Result<Matcher, String> {
+        if self.find.is_empty() {
+            return Err("nothing to find".to_string());
+        }
+        if self.regex {
+            match Regex::new(&self.find) {
+                Ok(re) => Ok(Matcher::Regex(re)),
+                Err(e) => Err(format!("invalid regular expression: {}", e)),
+            }
+        } else {
+            Ok(Matcher::Plain(self.find.clone()))
+        }
+    }
+
+    // The question, answer and note of the card after replacement
+    fn apply(&self, matcher: &Matcher, card: &Card) -> (String, String, String) {
+        let apply_to = |text: &str, enabled: bool| {
+            if enabled {
+                matcher.replace(text, &self.replacement)
+            } else {
+                text.to_string()
+            }
+        };
+        (
+            apply_to(&card.question, self.questions),
+            apply_to(&card.answer, self.answers),
+            apply_to(&card.note, self.notes),
+        )
+    }
+}
+
+impl Collection {
+    /// Lists the cards a find-and-replace would change, without changing them
+    pub fn preview_replace(&self, replace: &Replace) -> Result<Vec<ReplacePreview>, String> {
+        let matcher = replace.matcher()?;
+        let query = Query::parse(&replace.query)?;
+        let mut changes = vec![];
+        for (_, card) in self.search(&query) {
+            let before = (card.question.clone(), card.answer.clone(), card.note.clone());
+            let after = replace.apply(&matcher, card);
+            if before != after {
+                changes.push(ReplacePreview {
+                    uid: card.uid,
+                    before,
+                    after,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Applies a find-and-replace, returning the number of cards changed.
+    /// Every card is found before any is changed, so a failure changes none.
+    pub fn find_replace(&mut self, replace: &Replace) -> Result<usize, String> {
+        let changes = self.preview_replace(replace)?;
+        for change in &changes {
+            self.locate(change.uid)?;
+        }
+        for change in &changes {
+            let (question, answer, note) = change.after.clone();
+            self.edit_card(change.uid, question, answer, note)?;
+        }
+        Ok(changes.len())
+    }
+}
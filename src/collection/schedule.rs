@@ -18,6 +18,28 @@ impl SchedStage {
             Learning(duration) | Reviewing(duration) | Relearning(duration) => *duration,
         }
     }
+
+    /// How far along the card is, for comparing review histories. Ranks
+    /// stages by maturity, then by the interval within the stage.
+    pub(super) fn progress(&self) -> (u8, Duration) {
+        use SchedStage::*;
+        match self {
+            New => (0, Duration::from_secs(0)),
+            Learning(duration) => (1, *duration),
+            Relearning(duration) => (2, *duration),
+            Reviewing(duration) => (3, *duration),
+        }
+    }
+
+    pub(super) fn name(&self) -> &'static str {
+        use SchedStage::*;
+        match self {
+            New => "New",
+            Learning(_) => "Learning",
+            Reviewing(_) => "Reviewing",
+            Relearning(_) => "Relearning",
+        }
+    }
 }
 
 const DAY_IN_SECS: u64 = 86400;
@@ -32,7 +54,7 @@ const MONTH: Duration = Duration::from_secs(DAY_IN_SECS * 30);
 pub(super) fn schedule(stage: &SchedStage, success: bool) -> SchedStage {
     use SchedStage::*;
 
-    return match (stage, success) {
+    match (stage, success) {
         (New, true) => Learning(DAY),
         (New, false) => New,
         (Learning(duration), true) => {
@@ -53,5 +75,5 @@ pub(super) fn schedule(stage: &SchedStage, success: bool) -> SchedStage {
             }
         }
         (Relearning(_), false) => Relearning(DAY),
-    };
+    }
 }
@@ -0,0 +1,168 @@
+use std::error::Error;
+
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+use super::backup::BackupSettings;
+use super::data::Collection;
+use super::trash::Trash;
+
+/// Version of the format collections are saved in. Bump it along with adding
+/// a migration whenever a saved struct changes shape.
+pub const VERSION: u32 = 2;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+// Each migration upgrades a collection from the version of its position in the
+// list to the next one
+const MIGRATIONS: [Migration; VERSION as usize] = [fill_unversioned, add_note_uids];
+
+/// Turns a saved collection of any version into a `Collection`, upgrading it
+/// to the current format on the way
+pub fn load(mut value: Value) -> Result<Collection, Box<dyn Error>> {
+    upgrade(&mut value)?;
+    let mut collection: Collection = serde_json::from_value(value)?;
+    collection.reindex();
+    Ok(collection)
+}
+
+/// Runs whichever migrations a saved collection is missing, in order
+pub fn upgrade(value: &mut Value) -> Result<(), String> {
+    let collection = match value {
+        Value::Object(collection) => collection,
+        _ => return Err("a collection should be a JSON object".to_string()),
+    };
+    // Files from before versions were recorded are version 0
+    let version = match collection.get("version") {
+        Some(version) => match version.as_u64() {
+            Some(version) => version as u32,
+            None => return Err(format!("{} is not a collection version", version)),
+        },
+        None => 0,
+    };
+    if version > VERSION {
+        return Err(format!(
+            "the collection is version {}, made by a newer Ranki than this one, which reads up to {}",
+            version, VERSION
+        ));
+    }
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migrate(collection).map_err(|e| format!("upgrading from version {}: {}", from, e))?;
+        collection.insert("version".to_string(), json!(from as u32 + 1));
+    }
+    Ok(())
+}
+
+// ----- Version 0 to 1 -----
+// Unversioned files range from the original format, which had ids, titles and
+// the card content and schedule, to ones with any of the fields added before
+// versions existed. Fill in every one of those that's missing.
+fn fill_unversioned(collection: &mut Map<String, Value>) -> Result<(), String> {
+    let defaults = [
+        ("trash", to_value(Trash::default())?),
+        ("backup_settings", to_value(BackupSettings::default())?),
+    ];
+    fill(collection, &defaults);
+    for deck in objects(collection.get_mut("decks"))? {
+        fill_deck(deck)?;
+    }
+    if let Some(Value::Object(trash)) = collection.get_mut("trash") {
+        for trashed in objects(trash.get_mut("decks"))? {
+            if let Some(Value::Object(deck)) = trashed.get_mut("deck") {
+                fill_deck(deck)?;
+            }
+        }
+        for trashed in objects(trash.get_mut("cards"))? {
+            if let Some(Value::Object(card)) = trashed.get_mut("card") {
+                fill_card(card);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn fill_deck(deck: &mut Map<String, Value>) -> Result<(), String> {
+    let defaults = [
+        ("uid", json!(Uuid::new_v4())),
+        ("filter", Value::Null),
+        ("created", Value::Null),
+        ("modified", Value::Null),
+    ];
+    fill(deck, &defaults);
+    for card in objects(deck.get_mut("cards"))? {
+        fill_card(card);
+    }
+    Ok(())
+}
+
+fn fill_card(card: &mut Map<String, Value>) {
+    let defaults = [
+        ("uid", json!(Uuid::new_v4())),
+        ("tags", json!([])),
+        ("flag", Value::Null),
+        ("marked", json!(false)),
+        ("lapses", json!(0)),
+        ("failed", Value::Null),
+        ("home", Value::Null),
+        ("created", Value::Null),
+        ("modified", Value::Null),
+        ("revisions", json!([])),
+    ];
+    fill(card, &defaults);
+}
+
+// ----- Version 1 to 2 -----
+// Notes gained uids of their own
+fn add_note_uids(collection: &mut Map<String, Value>) -> Result<(), String> {
+    for deck in objects(collection.get_mut("decks"))? {
+        add_deck_note_uids(deck)?;
+    }
+    if let Some(Value::Object(trash)) = collection.get_mut("trash") {
+        for trashed in objects(trash.get_mut("decks"))? {
+            if let Some(Value::Object(deck)) = trashed.get_mut("deck") {
+                add_deck_note_uids(deck)?;
+            }
+        }
+        for trashed in objects(trash.get_mut("cards"))? {
+            if let Some(Value::Object(card)) = trashed.get_mut("card") {
+                fill(card, &[("note_uid", json!(Uuid::new_v4()))]);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_deck_note_uids(deck: &mut Map<String, Value>) -> Result<(), String> {
+    for card in objects(deck.get_mut("cards"))? {
+        fill(card, &[("note_uid", json!(Uuid::new_v4()))]);
+    }
+    Ok(())
+}
+
+// ----- Helpers -----
+fn fill(object: &mut Map<String, Value>, defaults: &[(&str, Value)]) {
+    for (key, value) in defaults {
+        if !object.contains_key(*key) {
+            object.insert(key.to_string(), value.clone());
+        }
+    }
+}
+
+// The objects in a list, which is allowed to be missing
+fn objects(list: Option<&mut Value>) -> Result<Vec<&mut Map<String, Value>>, String> {
+    match list {
+        None => Ok(vec![]),
+        Some(Value::Array(items)) => items
+            .iter_mut()
+            .map(|item| match item {
+                Value::Object(object) => Ok(object),
+                other => Err(format!("expected an object, found {}", other)),
+            })
+            .collect(),
+        Some(other) => Err(format!("expected a list, found {}", other)),
+    }
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<Value, String> {
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
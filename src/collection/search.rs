@@ -0,0 +1,158 @@
+use std::time::{Duration, SystemTime};
+
+use uuid::Uuid;
+
+use super::data::{Card, Deck, Flag};
+use super::schedule::SchedStage;
+
+/// A parsed search over the cards of a collection. A query is a whitespace
+/// separated list of terms, every one of which has to match for a card to be
+/// included. Terms can be wrapped in double quotes to include spaces, and
+/// prefixed with `-` to negate them. Supported terms:
+///
+/// - `deck:<title>`: the card's deck title, ignoring case
+/// - `id:<uid>`: the card with the given unique id, or whose note has it
+/// - `tag:<tag>`: one of the card's tags, ignoring case
+/// - `note:<text>`: text within the card's note, ignoring case
+/// - `flag:<colour>`, `flag:any`, `flag:none`: the card's flag
+/// - `is:marked`: cards marked as being of interest
+/// - `is:due`, `is:new`, `is:learning`, `is:review`, `is:relearning`
+/// - `failed:<days>`: forgotten during a quiz within the last number of days
+/// - anything else is matched as a substring of the question, answer or note
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    terms: Vec<(bool, Term)>,
+}
+
+#[derive(Clone, Debug)]
+enum Term {
+    Text(String),
+    Deck(String),
+    Id(Uuid),
+    Tag(String),
+    Note(String),
+    Colour(Option<Flag>),
+    Flagged,
+    Marked,
+    Failed(Duration),
+    Due,
+    New,
+    Learning,
+    Reviewing,
+    Relearning,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut terms = vec![];
+        for token in tokenize(query) {
+            let (negated, token) = match token.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, token),
+            };
+            terms.push((negated, Term::parse(&token)?));
+        }
+        Ok(Query { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, deck: &Deck, card: &Card) -> bool {
+        self.terms
+            .iter()
+            .all(|(negated, term)| term.matches(deck, card) != *negated)
+    }
+}
+
+impl Term {
+    fn parse(token: &str) -> Result<Self, String> {
+        let (key, value) = match token.find(':') {
+            Some(pos) => (&token[..pos], &token[pos + 1..]),
+            None => return Ok(Term::Text(token.to_lowercase())),
+        };
+        match key.to_lowercase().as_str() {
+            "deck" => Ok(Term::Deck(value.to_lowercase())),
+            "id" => match Uuid::parse_str(value) {
+                Ok(uid) => Ok(Term::Id(uid)),
+                Err(_) => Err(format!("expected a card or note uid in search: {}", token)),
+            },
+            "tag" => Ok(Term::Tag(value.to_lowercase())),
+            "note" => Ok(Term::Note(value.to_lowercase())),
+            "flag" => match value.to_lowercase().as_str() {
+                "any" => Ok(Term::Flagged),
+                "none" => Ok(Term::Colour(None)),
+                colour => match Flag::ALL.iter().find(|f| f.name().to_lowercase() == colour) {
+                    Some(flag) => Ok(Term::Colour(Some(*flag))),
+                    None => Err(format!("unknown flag colour in search: {}", value)),
+                },
+            },
+            "failed" => match value.parse::<u64>() {
+                Ok(days) => Ok(Term::Failed(Duration::from_secs(days * 86400))),
+                Err(_) => Err(format!("expected a number of days in search: {}", token)),
+            },
+            "is" => match value.to_lowercase().as_str() {
+                "due" => Ok(Term::Due),
+                "marked" => Ok(Term::Marked),
+                "new" => Ok(Term::New),
+                "learning" => Ok(Term::Learning),
+                "review" => Ok(Term::Reviewing),
+                "relearning" => Ok(Term::Relearning),
+                _ => Err(format!("unknown card state in search: {}", value)),
+            },
+            // Not a known key, so the colon is just part of the text
+            _ => Ok(Term::Text(token.to_lowercase())),
+        }
+    }
+
+    fn matches(&self, deck: &Deck, card: &Card) -> bool {
+        use Term::*;
+        match self {
+            Text(text) => {
+                card.question.to_lowercase().contains(text)
+                    || card.answer.to_lowercase().contains(text)
+                    || card.note.to_lowercase().contains(text)
+            }
+            Deck(title) => deck.title.to_lowercase() == *title,
+            Id(uid) => card.uid == *uid || card.note_uid == *uid,
+            Tag(tag) => card.tags.iter().any(|t| t.to_lowercase() == *tag),
+            Note(text) => card.note.to_lowercase().contains(text),
+            Colour(flag) => card.flag == *flag,
+            Flagged => card.flag.is_some(),
+            Marked => card.marked,
+            Failed(within) => match card.last_failed() {
+                Some(failed) => failed + *within >= SystemTime::now(),
+                None => false,
+            },
+            Due => card.due() < SystemTime::now(),
+            New => matches!(card.stage(), SchedStage::New),
+            Learning => matches!(card.stage(), SchedStage::Learning(_)),
+            Reviewing => matches!(card.stage(), SchedStage::Reviewing(_)),
+            Relearning => matches!(card.stage(), SchedStage::Relearning(_)),
+        }
+    }
+}
+
+/// Splits a query on whitespace, keeping double quoted sections together
+fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
@@ -0,0 +1,363 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use super::data::{Collection, Deck};
+use super::schema;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS config (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS decks (
+        uid TEXT PRIMARY KEY,
+        position INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cards (
+        uid TEXT PRIMARY KEY,
+        deck_uid TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        question TEXT NOT NULL,
+        answer TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS cards_by_deck ON cards (deck_uid, id);
+    CREATE TABLE IF NOT EXISTS review_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        card_uid TEXT NOT NULL,
+        reviewed INTEGER NOT NULL,
+        stage TEXT NOT NULL,
+        lapses INTEGER NOT NULL
+    );
+";
+
+/// Keeps a collection in an SQLite database with a row per deck and per card,
+/// so that saving only writes what changed since the last save. Each answer
+/// given during a quiz is also added to a review log.
+pub struct SqliteStore {
+    conn: Connection,
+    // What was last written for each config key, deck and card
+    config: HashMap<String, String>,
+    decks: HashMap<Uuid, SavedDeck>,
+    cards: HashMap<Uuid, SavedCard>,
+    // Changes whenever another connection commits to the database
+    data_version: i64,
+}
+
+struct SavedDeck {
+    position: usize,
+    data: String,
+    // The deck as it was saved. Decks are copied before being changed while
+    // this is held, so the same one still being in the collection means
+    // neither it nor its cards have changed.
+    deck: Arc<Deck>,
+}
+
+#[derive(PartialEq)]
+struct SavedCard {
+    deck_uid: Uuid,
+    data: String,
+    reviewed: SystemTime,
+}
+
+impl SqliteStore {
+    pub const FILE: &'static str = "anki.db";
+
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteStore {
+            conn,
+            config: HashMap::new(),
+            decks: HashMap::new(),
+            cards: HashMap::new(),
+            data_version: 0,
+        })
+    }
+
+    /// Whether a collection has ever been saved to the database
+    pub fn is_empty(&self) -> Result<bool, Box<dyn Error>> {
+        let count: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM config", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    pub fn load(&mut self) -> Result<Collection, Box<dyn Error>> {
+        if self.is_empty()? {
+            // Nothing is remembered, so the first save writes everything
+            println!("No collection in the database yet. Starting a new one.");
+            self.data_version = self.current_data_version()?;
+            return Ok(Collection::new());
+        }
+
+        // Put the rows back together into the shape the collection is
+        // serialised in, so that older versions are upgraded the same way
+        let mut collection = Map::new();
+        for (key, value) in self.query_pairs("SELECT key, value FROM config")? {
+            collection.insert(key, serde_json::from_str(&value)?);
+        }
+
+        let mut decks: Vec<Map<String, Value>> = vec![];
+        let mut deck_pos: HashMap<String, usize> = HashMap::new();
+        for (uid, data) in self.query_pairs("SELECT uid, data FROM decks ORDER BY position")? {
+            let mut deck: Map<String, Value> = serde_json::from_str(&data)?;
+            deck.insert("cards".to_string(), Value::Array(vec![]));
+            deck_pos.insert(uid, decks.len());
+            decks.push(deck);
+        }
+
+        for (deck_uid, data) in
+            self.query_pairs("SELECT deck_uid, data FROM cards ORDER BY deck_uid, id")?
+        {
+            let pos = match deck_pos.get(&deck_uid) {
+                Some(pos) => *pos,
+                None => {
+                    println!("error: skipping card of missing deck {}", deck_uid);
+                    continue;
+                }
+            };
+            if let Some(Value::Array(cards)) = decks[pos].get_mut("cards") {
+                cards.push(serde_json::from_str(&data)?);
+            }
+        }
+
+        collection.insert(
+            "decks".to_string(),
+            Value::Array(decks.into_iter().map(Value::Object).collect()),
+        );
+        let collection = schema::load(Value::Object(collection))?;
+        self.remember(&collection)?;
+        self.data_version = self.current_data_version()?;
+        Ok(collection)
+    }
+
+    /// Writes whatever changed since the last load or save in one transaction
+    pub fn save(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        let uids = unique_uids(collection)?;
+        let mut config = HashMap::new();
+        let mut decks = vec![];
+        let mut cards = vec![];
+        let tx = self.conn.transaction()?;
+
+        if let Value::Object(header) = collection.header() {
+            for (key, value) in header {
+                let value = value.to_string();
+                if self.config.get(&key) != Some(&value) {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO config (key, value) VALUES (?1, ?2)",
+                        params![key, value],
+                    )?;
+                }
+                config.insert(key, value);
+            }
+        }
+
+        for (pos, deck) in collection.decks.iter().enumerate() {
+            let previous = self.decks.get(&deck.uid);
+            // Neither the deck nor its cards have changed, though decks before
+            // it may have come or gone
+            if let Some(previous) = previous.filter(|saved| Arc::ptr_eq(&saved.deck, deck)) {
+                if previous.position != pos {
+                    tx.execute(
+                        "UPDATE decks SET position = ?2 WHERE uid = ?1",
+                        params![deck.uid.to_string(), pos as i64],
+                    )?;
+                    decks.push(SavedDeck {
+                        position: pos,
+                        data: previous.data.clone(),
+                        deck: Arc::clone(deck),
+                    });
+                }
+                continue;
+            }
+            let data = deck.header().to_string();
+            match previous {
+                None => {
+                    tx.execute(
+                        "INSERT INTO decks (uid, position, data) VALUES (?1, ?2, ?3)",
+                        params![deck.uid.to_string(), pos as i64, data],
+                    )?;
+                }
+                Some(previous) if previous.position != pos || previous.data != data => {
+                    tx.execute(
+                        "UPDATE decks SET position = ?2, data = ?3 WHERE uid = ?1",
+                        params![deck.uid.to_string(), pos as i64, data],
+                    )?;
+                }
+                Some(_) => {}
+            }
+            decks.push(SavedDeck {
+                position: pos,
+                data,
+                deck: Arc::clone(deck),
+            });
+
+            for card in &deck.cards {
+                let saved = SavedCard {
+                    deck_uid: deck.uid,
+                    data: serde_json::to_string(card)?,
+                    reviewed: card.last_reviewed(),
+                };
+                let previous = self.cards.get(&card.uid);
+                let sql = match previous {
+                    None => Some(
+                        "INSERT INTO cards (uid, deck_uid, id, question, answer, data)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    ),
+                    Some(previous) if *previous != saved => Some(
+                        "UPDATE cards SET deck_uid = ?2, id = ?3, question = ?4, answer = ?5,
+                         data = ?6 WHERE uid = ?1",
+                    ),
+                    Some(_) => None,
+                };
+                if let Some(sql) = sql {
+                    tx.execute(
+                        sql,
+                        params![
+                            card.uid.to_string(),
+                            deck.uid.to_string(),
+                            card.id,
+                            card.question,
+                            card.answer,
+                            saved.data
+                        ],
+                    )?;
+                }
+                // Undoing an answer moves the time back, which isn't a review
+                if let Some(previous) = previous {
+                    if saved.reviewed > previous.reviewed {
+                        tx.execute(
+                            "INSERT INTO review_log (card_uid, reviewed, stage, lapses)
+                             VALUES (?1, ?2, ?3, ?4)",
+                            params![
+                                card.uid.to_string(),
+                                secs_since_epoch(saved.reviewed),
+                                card.stage_name(),
+                                card.lapses()
+                            ],
+                        )?;
+                    }
+                }
+                cards.push((card.uid, saved));
+            }
+        }
+
+        for uid in self.decks.keys().filter(|uid| !uids.contains(uid)) {
+            tx.execute("DELETE FROM decks WHERE uid = ?1", params![uid.to_string()])?;
+        }
+        for uid in self.cards.keys().filter(|uid| !uids.contains(uid)) {
+            tx.execute("DELETE FROM cards WHERE uid = ?1", params![uid.to_string()])?;
+        }
+        tx.commit()?;
+
+        // Only now that it's all written, remember what was
+        self.config = config;
+        self.decks.retain(|uid, _| uids.contains(uid));
+        self.cards.retain(|uid, _| uids.contains(uid));
+        for saved in decks {
+            self.decks.insert(saved.deck.uid, saved);
+        }
+        self.cards.extend(cards);
+        self.data_version = self.current_data_version()?;
+        Ok(())
+    }
+
+    /// The collection as it is now, if another program wrote to the database
+    /// since the last load or save
+    pub fn load_external_changes(&mut self) -> Result<Option<Collection>, Box<dyn Error>> {
+        if self.current_data_version()? == self.data_version {
+            return Ok(None);
+        }
+        self.load().map(Some)
+    }
+
+    fn current_data_version(&self) -> Result<i64, Box<dyn Error>> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?)
+    }
+
+    fn query_pairs(&self, sql: &str) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        let mut pairs = vec![];
+        for row in rows {
+            pairs.push(row?);
+        }
+        Ok(pairs)
+    }
+
+    // Takes note of what is already in the database after loading it
+    fn remember(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        self.config.clear();
+        self.decks.clear();
+        self.cards.clear();
+        if let Value::Object(header) = collection.header() {
+            for (key, value) in header {
+                self.config.insert(key, value.to_string());
+            }
+        }
+        for (pos, deck) in collection.decks.iter().enumerate() {
+            let saved = SavedDeck {
+                position: pos,
+                data: deck.header().to_string(),
+                deck: Arc::clone(deck),
+            };
+            self.decks.insert(deck.uid, saved);
+            for card in &deck.cards {
+                self.cards.insert(
+                    card.uid,
+                    SavedCard {
+                        deck_uid: deck.uid,
+                        data: serde_json::to_string(card)?,
+                        reviewed: card.last_reviewed(),
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+// The uid of every deck and card in the collection. Rows are keyed by uid, so
+// two sharing one would be saved over each other, and the collection is
+// refused until checking it has given them uids of their own.
+fn unique_uids(collection: &Collection) -> Result<HashSet<Uuid>, Box<dyn Error>> {
+    let mut uids = HashSet::new();
+    for deck in &collection.decks {
+        if !uids.insert(deck.uid) {
+            return Err(format!(
+                "deck \"{}\" shares its uid with another deck or card, check the collection \
+                 to give it a new one",
+                deck.title
+            )
+            .into());
+        }
+        for card in &deck.cards {
+            if !uids.insert(card.uid) {
+                return Err(format!(
+                    "card {} of deck \"{}\" shares its uid with another deck or card, check \
+                     the collection to give it a new one",
+                    card.id, deck.title
+                )
+                .into());
+            }
+        }
+    }
+    Ok(uids)
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs() as i64
+}
@@ -0,0 +1,599 @@
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use super::backup::{self, Backups};
+use super::check::CheckReport;
+use super::crypt::{self, Cipher, WrongPassphrase};
+use super::data::Collection;
+use super::schema;
+use super::sqlite::SqliteStore;
+
+/// Somewhere a collection is kept between runs
+pub trait Storage: Send {
+    fn load(&mut self) -> Result<Collection, Box<dyn Error>>;
+    fn save(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>>;
+
+    /// The collection as it is now, if something else changed it since the
+    /// last load or save
+    fn load_external_changes(&mut self) -> Result<Option<Collection>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    /// Turns encrypting saves on or off, where the storage can do it
+    fn set_encrypted(&mut self, encrypted: bool) -> Result<(), Box<dyn Error>> {
+        if encrypted {
+            return Err("only the json storage can be encrypted".into());
+        }
+        Ok(())
+    }
+
+    /// What saves are encrypted with, if they are
+    fn cipher(&self) -> Option<Cipher> {
+        None
+    }
+
+    /// Changes the format saves are written in, where the storage has a choice
+    fn set_format(&mut self, format: Format) -> Result<(), Box<dyn Error>> {
+        if format != Format::Json {
+            return Err("only the json storage has a compact format".into());
+        }
+        Ok(())
+    }
+
+    /// The format saves are written in
+    fn format(&self) -> Format {
+        Format::Json
+    }
+}
+
+/// How the JSON storage writes the collection. The compact format is the same
+/// JSON compressed with gzip, so older collections still upgrade through the
+/// migrations in `schema`, at around an eighth of the size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Compact,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "json" => Ok(Format::Json),
+            "compact" => Ok(Format::Compact),
+            _ => Err(format!("unknown format {}, expected json or compact", name)),
+        }
+    }
+}
+
+// Starts every gzip stream, where JSON starts with a bracket or whitespace
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Added to the name of a collection file set aside as unreadable, followed by
+// the time it was set aside, in seconds
+const CORRUPT_SUFFIX: &str = ".corrupt-";
+
+/// Picks the storage to use by name, one of "json", "sqlite" or "memory",
+/// keeping its files in the given directory. Without a name the SQLite
+/// database is used if there is one, and the JSON file otherwise. An existing
+/// JSON collection is copied into a new database. Encrypted JSON collections
+/// need the passphrase they were encrypted with.
+pub fn open(
+    kind: Option<&str>,
+    dir: &Path,
+    passphrase: Option<&str>,
+) -> Result<Box<dyn Storage>, Box<dyn Error>> {
+    let cipher = passphrase.map(Cipher::new);
+    fs::create_dir_all(dir)?;
+    let json_path = dir.join(JsonStore::FILE);
+    let sqlite_path = dir.join(SqliteStore::FILE);
+    let kind = match kind {
+        Some(kind) => kind,
+        None if sqlite_path.exists() => "sqlite",
+        None => "json",
+    };
+    match kind {
+        "json" => Ok(Box::new(JsonStore::new(json_path).with_cipher(cipher))),
+        "memory" => Ok(Box::new(MemoryStore::default())),
+        "sqlite" => {
+            let mut store = SqliteStore::open(&sqlite_path)?;
+            if store.is_empty()? && json_path.exists() {
+                if is_encrypted(dir) {
+                    return Err("an encrypted collection can't be copied into a database".into());
+                }
+                let mut collection = JsonStore::new(&json_path).load()?;
+                // The database keeps decks and cards by uid, so any sharing
+                // one need new ones before they can be copied in
+                let mut report = CheckReport::default();
+                collection.check_uids(&mut report);
+                for repaired in &report.repaired {
+                    println!("Repaired: {}", repaired);
+                }
+                store.save(&collection)?;
+                println!(
+                    "Copied collection from {} into {}.",
+                    json_path.display(),
+                    sqlite_path.display()
+                );
+            }
+            Ok(Box::new(store))
+        }
+        _ => Err(format!("unknown storage {}, expected json, sqlite or memory", kind).into()),
+    }
+}
+
+/// Whether the JSON collection in a directory is encrypted, and so needs a
+/// passphrase to open
+pub fn is_encrypted(dir: &Path) -> bool {
+    file_is_encrypted(&dir.join(JsonStore::FILE))
+}
+
+fn file_is_encrypted(path: &Path) -> bool {
+    let mut start = vec![];
+    match File::open(path) {
+        Ok(file) => file.take(64).read_to_end(&mut start).is_ok() && crypt::is_encrypted(&start),
+        Err(_) => false,
+    }
+}
+
+/// The directory the collection is kept in unless another is given, under the
+/// XDG data directory, e.g. `~/.local/share/ranki`
+pub fn default_dir() -> Result<PathBuf, Box<dyn Error>> {
+    match dirs::data_dir() {
+        Some(dir) => Ok(dir.join("ranki")),
+        None => Err("no data directory found, pass one with --collection".into()),
+    }
+}
+
+// Moves the collection files and backups in one directory into another, unless
+// there's nothing to move or the other directory has a collection of its own
+pub(super) fn move_collection(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    let files = [JsonStore::FILE, SqliteStore::FILE];
+    let found = match files.iter().map(|name| from.join(name)).find(|path| path.exists()) {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    if files.iter().any(|name| to.join(name).exists()) {
+        println!(
+            "Left {} where it is, as {} has a collection already.",
+            found.display(),
+            to.display()
+        );
+        return Ok(());
+    }
+    fs::create_dir_all(to)?;
+    for name in files.iter() {
+        let source = from.join(name);
+        if source.exists() {
+            let target = to.join(name);
+            move_path(&source, &target)?;
+            println!("Moved {} to {}.", source.display(), target.display());
+        }
+    }
+
+    // The directory may not be Ranki's own, so only files named like backups
+    // are taken from its backups directory, which is left if anything remains
+    let backups = from.join(Backups::DIR);
+    if !backups.is_dir() {
+        return Ok(());
+    }
+    let target = to.join(Backups::DIR);
+    for entry in fs::read_dir(&backups)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !entry.path().is_file() || !backup::is_backup_name(&name.to_string_lossy()) {
+            continue;
+        }
+        fs::create_dir_all(&target)?;
+        move_path(&entry.path(), &target.join(&name))?;
+        println!("Moved {} to {}.", entry.path().display(), target.display());
+    }
+    let _ = fs::remove_dir(&backups);
+    Ok(())
+}
+
+// Renaming fails when the two paths are on different file systems, in which
+// case everything is copied over before the original is removed. A copy that
+// fails part way is removed again.
+fn move_path(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    if let Err(e) = copy_path(from, to) {
+        let _ = if to.is_dir() {
+            fs::remove_dir_all(to)
+        } else {
+            fs::remove_file(to)
+        };
+        return Err(e);
+    }
+    if from.is_dir() {
+        fs::remove_dir_all(from)?;
+    } else {
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+// Copies a file, or a directory along with everything in it
+fn copy_path(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Keeps the whole collection in a single JSON file. Saves are written to a
+/// temporary file first and renamed over the collection once safely on disk,
+/// so a crash part way through never leaves a half written collection. It can
+/// also be compressed, and encrypted with a passphrase.
+pub struct JsonStore {
+    path: PathBuf,
+    cipher: Option<Cipher>,
+    /// Whether saves are encrypted, which they are if the file was
+    encrypted: bool,
+    /// Format of saves, the one the file was in unless it's changed
+    format: Format,
+    // Modification time and size of the file as last loaded or saved
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl JsonStore {
+    pub const FILE: &'static str = "anki.conf";
+
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        JsonStore {
+            path: path.as_ref().to_path_buf(),
+            cipher: None,
+            encrypted: false,
+            format: Format::Json,
+            stamp: None,
+        }
+    }
+
+    /// Gives the passphrase to decrypt the collection with, and to encrypt it
+    /// with if it's encrypted already or encryption is turned on
+    pub fn with_cipher(mut self, cipher: Option<Cipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn current_stamp(&self) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(&self.path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        with_suffix(&self.path, ".tmp")
+    }
+
+    /// Reads a collection in any format, also saying how the file was written.
+    /// The format can only be told once the file is decrypted.
+    pub(super) fn read(
+        path: &Path,
+        cipher: Option<&mut Cipher>,
+    ) -> Result<ReadCollection, Box<dyn Error>> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let encrypted = crypt::is_encrypted(&bytes);
+        if encrypted {
+            bytes = match cipher {
+                Some(cipher) => cipher.decrypt(&bytes)?,
+                None => return Err(WrongPassphrase.into()),
+            };
+        }
+        let format = if bytes.starts_with(&GZIP_MAGIC) {
+            let mut json = vec![];
+            GzDecoder::new(&bytes[..]).read_to_end(&mut json)?;
+            bytes = json;
+            Format::Compact
+        } else {
+            Format::Json
+        };
+        Ok(ReadCollection {
+            collection: schema::load(serde_json::from_slice(&bytes)?)?,
+            format,
+            encrypted,
+        })
+    }
+
+    /// Saves over the file whether or not it was changed outside of Ranki
+    pub(super) fn overwrite(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        self.stamp = self.current_stamp();
+        self.save(collection)
+    }
+
+    fn load_or_recover(&mut self) -> Result<ReadCollection, Box<dyn Error>> {
+        if !self.path.exists() {
+            // Saves rename the temporary file into place, so one left behind
+            // without a collection file is from an interrupted first save
+            let temp = self.temp_path();
+            if let Ok(read) = JsonStore::read(&temp, self.cipher.as_mut()) {
+                println!("Recovered collection from an unfinished save.");
+                return Ok(read);
+            }
+            self.refuse_if_unrecovered()?;
+            println!("No collection configuration file found. Creating new file.");
+            return Ok(ReadCollection {
+                collection: Collection::new(),
+                format: self.format,
+                encrypted: self.encrypted,
+            });
+        }
+
+        match JsonStore::read(&self.path, self.cipher.as_mut()) {
+            Ok(read) => Ok(read),
+            // The file is fine, it's the passphrase that isn't
+            Err(error) if error.is::<WrongPassphrase>() => Err(error),
+            Err(error) => self.recover(error),
+        }
+    }
+
+    // Called when the collection file can't be read. A complete temporary file
+    // means a save got as far as writing it but not renaming it, so it holds
+    // the newest collection. Otherwise the unreadable file is set aside rather
+    // than being overwritten, and the newest backup that can be read takes its
+    // place.
+    fn recover(&mut self, error: Box<dyn Error>) -> Result<ReadCollection, Box<dyn Error>> {
+        let temp = self.temp_path();
+        if let Ok(read) = JsonStore::read(&temp, self.cipher.as_mut()) {
+            println!(
+                "{} could not be read ({}), recovered it from an unfinished save.",
+                self.path.display(),
+                error
+            );
+            fs::rename(&temp, &self.path)?;
+            return Ok(read);
+        }
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let corrupt = with_suffix(&self.path, &format!("{}{}", CORRUPT_SUFFIX, secs));
+        fs::rename(&self.path, &corrupt)?;
+        let mut backups = self.backups();
+        if let Some((name, read)) = backups.newest() {
+            println!(
+                "{} could not be read ({}), it has been moved to {} and the collection \
+                 restored from backup {}.",
+                self.path.display(),
+                error,
+                corrupt.display(),
+                name
+            );
+            return Ok(read);
+        }
+        Err(format!(
+            "{} could not be read ({}), it has been moved to {}",
+            self.path.display(),
+            error,
+            corrupt.display()
+        )
+        .into())
+    }
+
+    // A missing collection file is normally a new collection, unless it's
+    // missing because it was set aside as unreadable with no backup to take its
+    // place. Starting afresh then would leave an empty collection to be saved
+    // over everything, so the file has to be dealt with first.
+    fn refuse_if_unrecovered(&self) -> Result<(), Box<dyn Error>> {
+        let (corrupt, set_aside) = match self.newest_corrupt() {
+            Some(corrupt) => corrupt,
+            None => return Ok(()),
+        };
+        if self.backups().newest_taken().is_some_and(|taken| taken >= set_aside) {
+            return Ok(());
+        }
+        Err(format!(
+            "{} is missing, and the last copy of it, {}, could not be read. Repair it and \
+             move it back, or delete it to start a new collection.",
+            self.path.display(),
+            corrupt.display()
+        )
+        .into())
+    }
+
+    // The newest copy of the collection file set aside as unreadable, and when
+    // it was set aside
+    fn newest_corrupt(&self) -> Option<(PathBuf, SystemTime)> {
+        let name = self.path.file_name()?.to_string_lossy().to_string();
+        let prefix = format!("{}{}", name, CORRUPT_SUFFIX);
+        let dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let secs = entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_prefix(&prefix)?
+                    .parse::<u64>()
+                    .ok()?;
+                Some((entry.path(), UNIX_EPOCH + Duration::from_secs(secs)))
+            })
+            .max_by_key(|(_, set_aside)| *set_aside)
+    }
+
+    // The backups kept next to the collection
+    fn backups(&self) -> Backups {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        let mut backups = Backups::new(dir.join(Backups::DIR));
+        backups.set_cipher(self.cipher.clone());
+        backups
+    }
+}
+
+/// A collection read from a file, along with how the file was written, so that
+/// saves carry on the same way
+pub(super) struct ReadCollection {
+    pub collection: Collection,
+    pub format: Format,
+    pub encrypted: bool,
+}
+
+impl Storage for JsonStore {
+    fn load(&mut self) -> Result<Collection, Box<dyn Error>> {
+        let read = self.load_or_recover()?;
+        self.format = read.format;
+        self.encrypted = read.encrypted;
+        self.stamp = self.current_stamp();
+        Ok(read.collection)
+    }
+
+    fn save(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        // Something else wrote the file since it was last read, and it hasn't
+        // been taken up yet, maybe as it's still being written
+        if self.current_stamp() != self.stamp {
+            return Err(format!(
+                "{} was changed outside of Ranki and hasn't been reloaded yet",
+                self.path.display()
+            )
+            .into());
+        }
+        let temp = self.temp_path();
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp)?;
+        let mut bytes = serde_json::to_vec(collection)?;
+        if self.format == Format::Compact {
+            // Every change is saved, and the best compression takes several
+            // times as long for little more saving
+            let mut encoder = GzEncoder::new(vec![], Compression::fast());
+            encoder.write_all(&bytes)?;
+            bytes = encoder.finish()?;
+        }
+        if self.encrypted {
+            bytes = match &mut self.cipher {
+                Some(cipher) => cipher.encrypt(&bytes)?,
+                None => return Err("no passphrase to encrypt the collection with".into()),
+            };
+        }
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)?;
+        sync_parent(&self.path);
+        self.stamp = self.current_stamp();
+        Ok(())
+    }
+
+    // Unlike loading on startup, a file that can't be read is left where it
+    // is, as it may be half way through being written. It's tried again until
+    // it can be read, and not saved over in the meantime.
+    fn load_external_changes(&mut self) -> Result<Option<Collection>, Box<dyn Error>> {
+        let stamp = self.current_stamp();
+        if stamp.is_none() || stamp == self.stamp {
+            return Ok(None);
+        }
+        let read = JsonStore::read(&self.path, self.cipher.as_mut())?;
+        self.stamp = stamp;
+        Ok(Some(read.collection))
+    }
+
+    fn set_encrypted(&mut self, encrypted: bool) -> Result<(), Box<dyn Error>> {
+        if encrypted && self.cipher.is_none() {
+            return Err("a passphrase is needed to encrypt the collection".into());
+        }
+        self.encrypted = encrypted;
+        Ok(())
+    }
+
+    fn cipher(&self) -> Option<Cipher> {
+        if self.encrypted {
+            self.cipher.clone()
+        } else {
+            None
+        }
+    }
+
+    fn set_format(&mut self, format: Format) -> Result<(), Box<dyn Error>> {
+        self.format = format;
+        Ok(())
+    }
+
+    fn format(&self) -> Format {
+        self.format
+    }
+}
+
+pub(super) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Flushes the rename itself to disk. Not every platform can open a directory
+// to sync it, in which case the rename is left to the OS.
+pub(super) fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Keeps the collection in memory only, so nothing outlives the run. Handy
+/// for trying things out and for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    collection: Option<Collection>,
+}
+
+impl MemoryStore {
+    pub fn new(collection: Collection) -> Self {
+        MemoryStore {
+            collection: Some(collection),
+        }
+    }
+}
+
+impl Storage for MemoryStore {
+    fn load(&mut self) -> Result<Collection, Box<dyn Error>> {
+        match &self.collection {
+            Some(collection) => Ok(collection.clone()),
+            None => Ok(Collection::new()),
+        }
+    }
+
+    fn save(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        self.collection = Some(collection.clone());
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStore {
+    fn load(&mut self) -> Result<Collection, Box<dyn Error>> {
+        SqliteStore::load(self)
+    }
+
+    fn save(&mut self, collection: &Collection) -> Result<(), Box<dyn Error>> {
+        SqliteStore::save(self, collection)
+    }
+
+    fn load_external_changes(&mut self) -> Result<Option<Collection>, Box<dyn Error>> {
+        SqliteStore::load_external_changes(self)
+    }
+}
@@ -1,146 +1,638 @@
 use std::error::Error;
+use std::mem;
 use std::sync::mpsc;
+use std::time::Duration;
 
+use uuid::Uuid;
+
+use super::backup::{BackupSettings, Backups};
+use super::check::CheckReport;
+use super::crypt::WrongPassphrase;
 use super::data;
+use super::history::History;
+use super::profile::Profiles;
+use super::replace::Replace;
+use super::storage::{self, Format, Storage};
 
+/// Changes to make to the collection, and requests for what it holds. Cards
+/// are given by uid, which stays the same wherever they're moved.
 pub enum Action {
     AddDeck(String),
     DeleteDeck(u32),
     EditDeck(u32, String),
-    AddCard(u32, String, String),
-    DeleteCard(u32, u32),
-    EditCard(u32, u32, String, String),
-    GetQuiz,
-    QuizResults(Vec<(u32, u32, Option<bool>)>),
+    AddFilteredDeck(String, String, bool),
+    RebuildFilteredDeck(u32),
+    EmptyFilteredDeck(u32),
+    AddCard(u32, String, String, String),
+    DeleteCard(Uuid),
+    EditCard(Uuid, String, String, String),
+    RevertCard(Uuid, usize),
+    SetTags(Uuid, Vec<String>),
+    SetFlag(Uuid, Option<data::Flag>),
+    SetMarked(Uuid, bool),
+    MergeCards(Vec<Uuid>),
+    FindReplace(Replace),
+    RestoreDeck(u32),
+    RestoreCard(Uuid),
+    PurgeDeck(u32),
+    PurgeCard(Uuid),
+    EmptyTrash,
+    SetTrashRetention(u32),
+    BackUp,
+    ListBackups,
+    RestoreBackup(String),
+    SetBackupSettings(BackupSettings),
+    ListProfiles,
+    CreateProfile(String),
+    RenameProfile(u32, String),
+    DeleteProfile(u32),
+    /// Opens another profile, with the passphrase given for it if it's
+    /// encrypted differently from the one opened at startup
+    SwitchProfile(u32, Option<String>),
+    /// Takes the stored collection over the one in memory, after a conflict
+    ReloadCollection,
+    /// Keeps the collection in memory, overwriting the stored one
+    KeepCollection,
+    /// Checks the collection for broken invariants, repairing what it can
+    CheckCollection,
+    GetQuiz(Option<Vec<u32>>),
+    GetCram(Vec<u32>, data::CramOrder),
+    QuizResults(Vec<(Uuid, Option<bool>)>),
+    Undo,
+    Redo,
+    /// Sent as the app closes, so a last backup can be taken
+    Quit,
+}
+
+impl Action {
+    /// Describes the edit an action makes, for listing it in the undo
+    /// history. Actions that leave the collection alone have none.
+    fn label(&self) -> Option<&'static str> {
+        use Action::*;
+        match self {
+            AddDeck(_) => Some("Add Deck"),
+            DeleteDeck(_) => Some("Delete Deck"),
+            EditDeck(..) => Some("Rename Deck"),
+            AddFilteredDeck(..) => Some("Add Filtered Deck"),
+            RebuildFilteredDeck(_) => Some("Rebuild Filtered Deck"),
+            EmptyFilteredDeck(_) => Some("Empty Filtered Deck"),
+            AddCard(..) => Some("Add Card"),
+            DeleteCard(..) => Some("Delete Card"),
+            EditCard(..) => Some("Edit Card"),
+            RevertCard(..) => Some("Revert Card"),
+            SetTags(..) => Some("Set Tags"),
+            SetFlag(..) => Some("Flag Card"),
+            SetMarked(..) => Some("Mark Card"),
+            MergeCards(_) => Some("Merge Duplicates"),
+            FindReplace(_) => Some("Find and Replace"),
+            RestoreDeck(_) => Some("Restore Deck"),
+            RestoreCard(..) => Some("Restore Card"),
+            PurgeDeck(_) => Some("Purge Deck"),
+            PurgeCard(..) => Some("Purge Card"),
+            EmptyTrash => Some("Empty Trash"),
+            SetTrashRetention(_) => Some("Set Trash Retention"),
+            RestoreBackup(_) => Some("Restore Backup"),
+            SetBackupSettings(_) => Some("Backup Settings"),
+            ReloadCollection => Some("Reload Collection"),
+            CheckCollection => Some("Check Collection"),
+            QuizResults(_) => Some("Quiz Answers"),
+            // Profiles are separate collections, outside of any one's history
+            ListProfiles | CreateProfile(_) | RenameProfile(..) | DeleteProfile(_)
+            | SwitchProfile(..) => None,
+            GetQuiz(_) | GetCram(..) | BackUp | ListBackups | KeepCollection | Undo | Redo
+            | Quit => None,
+        }
+    }
 }
 
+// How often to look for changes made to the stored collection by something else
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 pub struct CollectionService {
+    backups: Backups,
     collection: data::Collection,
+    /// Whether the GUI has been asked to choose between the collection here
+    /// and one changed outside of Ranki. Nothing is saved until it answers,
+    /// so the outside change is still there to reload.
+    conflict: bool,
+    history: History,
+    kind: Option<String>,
+    /// Given at startup, for opening encrypted collections
+    passphrase: Option<String>,
+    /// Id of the open profile. Collections restored from elsewhere carry the
+    /// id they were saved with, so it's kept here rather than read off them.
+    profile: u32,
+    profiles: Profiles,
+    storage: Box<dyn Storage>,
     tx: mpsc::Sender<data::SendData>,
+    /// Whether the last save failed, leaving changes only in memory. Every
+    /// batch of actions is saved as soon as it's applied, so otherwise the
+    /// collection here is always the stored one.
+    unsaved: bool,
 }
 
 impl CollectionService {
-    pub fn new(tx: mpsc::Sender<data::SendData>) -> Result<CollectionService, Box<dyn Error>> {
-        let collection = data::Collection::load_from_file()?;
+    pub fn new(
+        tx: mpsc::Sender<data::SendData>,
+        kind: Option<String>,
+        passphrase: Option<String>,
+        mut profiles: Profiles,
+        profile: u32,
+    ) -> Result<CollectionService, Box<dyn Error>> {
+        let (storage, collection) =
+            open_profile(kind.as_deref(), passphrase.as_deref(), &profiles, profile)?;
+        profiles.set_last(profile)?;
+        let mut backups = Backups::new(profiles.dir_of(profile).join(Backups::DIR));
+        backups.set_cipher(storage.cipher());
+        backups.set_format(storage.format());
 
-        let service = CollectionService { collection, tx };
+        let mut service = CollectionService {
+            backups,
+            collection,
+            conflict: false,
+            history: History::new(),
+            kind,
+            passphrase,
+            profile,
+            profiles,
+            storage,
+            tx,
+            unsaved: false,
+        };
 
-        if let Err(e) = service.send_update() {
-            println!("error sending initial collection: {}", e);
-        }
+        service.send_update();
+        // Write back straight away so ids generated for an older file on load
+        // stay the same from then on
+        service.save();
+        service.back_up();
 
         Ok(service)
     }
 
+    /// Turns encryption of the open collection and its backups on or off
+    pub fn set_encrypted(&mut self, encrypted: bool) -> Result<(), Box<dyn Error>> {
+        let old = self.storage.cipher();
+        self.storage.set_encrypted(encrypted)?;
+        self.storage.save(&self.collection)?;
+        self.backups.set_cipher(self.storage.cipher());
+        self.backups.reencrypt(old)
+    }
+
+    /// Checks the open collection, saving whatever was repaired
+    pub fn check(&mut self) -> Result<CheckReport, Box<dyn Error>> {
+        let report = self.collection.check();
+        if !report.repaired.is_empty() {
+            self.storage.save(&self.collection)?;
+        }
+        Ok(report)
+    }
+
+    /// Rewrites the open collection, and later backups, in another format
+    pub fn set_format(&mut self, format: Format) -> Result<(), Box<dyn Error>> {
+        self.storage.set_format(format)?;
+        self.backups.set_format(self.storage.format());
+        self.storage.save(&self.collection)
+    }
+
     pub fn listen(&mut self, rx: mpsc::Receiver<Action>) {
-        for event in rx {
-            self.handle_event(event);
+        loop {
+            let action = match rx.recv_timeout(WATCH_INTERVAL) {
+                Ok(action) => Some(action),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            };
+            // Take up outside changes before applying any more, so that
+            // saving doesn't overwrite them
+            let mut changed = self.take_external_changes();
+            // Work through whatever else is already queued up before sending
+            // the collection back, so a burst of actions costs one update and
+            // one save instead of one each
+            let mut quit = false;
+            for action in action.into_iter().chain(rx.try_iter()) {
+                match action {
+                    Action::Quit => quit = true,
+                    action => changed |= self.handle_event(action),
+                }
+            }
+            if changed {
+                self.send_update();
+                self.send_history();
+                self.save();
+                if self.backups.due(&self.collection.backup_settings) {
+                    self.back_up();
+                }
+            }
+            if quit {
+                self.back_up();
+                return;
+            }
         }
     }
 
-    fn handle_event(&mut self, action: Action) {
+    /// Applies an action to the collection, returning whether it changed
+    fn handle_event(&mut self, action: Action) -> bool {
         use Action::*;
 
+        // Keep hold of the collection as it was, in case the action is undone
+        let before = action
+            .label()
+            .map(|label| (label, self.collection.clone()));
+
         match action {
             AddDeck(deck_name) => {
                 self.collection.add_deck(&deck_name);
-                if let Err(e) = self.send_update() {
-                    println!("error sending post add update: {}", e);
-                }
             }
             DeleteDeck(id) => {
-                if let None = self.collection.remove_deck_by_id(id) {
-                    println!("error: couldn't remove deck of id: {}", id);
+                if let Err(e) = self.collection.trash_deck(id) {
+                    println!("error: couldn't remove deck: {}", e);
+                    return false;
                 }
-                if let Err(e) = self.send_update() {
-                    println!("error sending post delete update {}", e);
+            }
+            EditDeck(id, new_name) => {
+                if let Err(e) = self.collection.rename_deck(id, new_name) {
+                    println!("error editing deck: {}", e);
+                    return false;
                 }
             }
-            EditDeck(id, new_name) => match self.collection.deck_pos_by_id(id) {
-                Some(pos) => {
-                    self.collection.decks[pos].title = new_name;
-                    if let Err(e) = self.send_update() {
-                        println!("error sending post edit update: {}", e);
-                    }
+            AddFilteredDeck(title, query, reschedule) => {
+                if let Err(e) = self.collection.add_filtered_deck(&title, &query, reschedule) {
+                    println!("error adding filtered deck {}: {}", title, e);
+                    return false;
+                }
+            }
+            RebuildFilteredDeck(id) => {
+                if let Err(e) = self.collection.rebuild_filtered_deck(id) {
+                    println!("error rebuilding filtered deck of id {}: {}", id, e);
+                    return false;
+                }
+            }
+            EmptyFilteredDeck(id) => {
+                if let Err(e) = self.collection.empty_filtered_deck(id) {
+                    println!("error emptying filtered deck of id {}: {}", id, e);
+                    return false;
+                }
+            }
+            AddCard(deck_id, question, answer, note) => {
+                if let Err(e) = self.collection.add_card(deck_id, question, answer, note) {
+                    println!("error adding card to deck id {}: {}", deck_id, e);
+                    return false;
+                }
+            }
+            DeleteCard(uid) => {
+                if let Err(e) = self.collection.trash_card(uid) {
+                    println!("error deleting card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            EditCard(uid, question, answer, note) => {
+                if let Err(e) = self.collection.edit_card(uid, question, answer, note) {
+                    println!("error editing card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            RevertCard(uid, revision) => {
+                if let Err(e) = self.collection.revert_card(uid, revision) {
+                    println!("error reverting card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            SetTags(uid, tags) => {
+                if let Err(e) = self.collection.set_tags(uid, tags) {
+                    println!("error setting tags on card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            SetFlag(uid, flag) => {
+                if let Err(e) = self.collection.set_flag(uid, flag) {
+                    println!("error setting flag on card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            SetMarked(uid, marked) => {
+                if let Err(e) = self.collection.set_marked(uid, marked) {
+                    println!("error marking card {}: {}", uid, e);
+                    return false;
+                }
+            }
+            MergeCards(cards) => {
+                if let Err(e) = self.collection.merge_cards(&cards) {
+                    println!("error merging duplicate cards: {}", e);
+                    return false;
+                }
+            }
+            FindReplace(replace) => {
+                if let Err(e) = self.collection.find_replace(&replace) {
+                    println!("error replacing {} in cards: {}", replace.find, e);
+                    return false;
+                }
+            }
+            GetQuiz(deck_ids) => {
+                let quiz = self.collection.get_quiz(deck_ids.as_deref());
+                if let Err(e) = self.tx.send(data::SendData::Quiz(quiz)) {
+                    println!("error sending quiz: {}", e);
+                }
+                return false;
+            }
+            GetCram(deck_ids, order) => {
+                let quiz = self.collection.get_cram(&deck_ids, order);
+                if let Err(e) = self.tx.send(data::SendData::Cram(quiz)) {
+                    println!("error sending cram quiz: {}", e);
+                }
+                return false;
+            }
+            RestoreDeck(id) => {
+                if let Err(e) = self.collection.restore_deck(id) {
+                    println!("error restoring deck: {}", e);
+                    return false;
+                }
+            }
+            RestoreCard(uid) => {
+                if let Err(e) = self.collection.restore_card(uid) {
+                    println!("error restoring card: {}", e);
+                    return false;
+                }
+            }
+            PurgeDeck(id) => {
+                if let Err(e) = self.collection.purge_deck(id) {
+                    println!("error purging deck: {}", e);
+                    return false;
+                }
+            }
+            PurgeCard(uid) => {
+                if let Err(e) = self.collection.purge_card(uid) {
+                    println!("error purging card: {}", e);
+                    return false;
+                }
+            }
+            EmptyTrash => {
+                self.collection.empty_trash();
+            }
+            SetTrashRetention(days) => {
+                self.collection.trash_mut().retention_days = days;
+                self.collection.purge_expired_trash();
+            }
+            QuizResults(results) => {
+                if let Err(e) = self.collection.process_results(results) {
+                    println!("error while processing quiz result: {}", e);
+                    return false;
+                }
+            }
+            BackUp => {
+                self.back_up();
+                self.send_backups();
+                return false;
+            }
+            ListBackups => {
+                self.send_backups();
+                return false;
+            }
+            RestoreBackup(name) => match self.backups.load(&name) {
+                Ok(collection) => {
+                    // Keep what's being replaced, in case the wrong one was picked
+                    self.back_up();
+                    self.collection = self.with_profile(collection);
+                    self.send_backups();
                 }
-                None => {
-                    println!("error editing deck of id {}, does not exist", id);
-                    return;
+                Err(e) => {
+                    println!("error restoring backup: {}", e);
+                    return false;
                 }
             },
-            AddCard(deck_id, question, answer) => {
-                match self.collection.add_card(deck_id, question, answer) {
-                    Ok(_) => {
-                        if let Err(e) = self.send_update() {
-                            println!("error sending post add card update: {}", e);
-                        }
-                    }
+            SetBackupSettings(settings) => {
+                self.collection.backup_settings = settings;
+            }
+            ListProfiles => {
+                self.send_profiles();
+                return false;
+            }
+            CreateProfile(name) => {
+                if let Err(e) = self.profiles.create(&name) {
+                    println!("error creating profile {}: {}", name, e);
+                }
+                self.send_profiles();
+                return false;
+            }
+            RenameProfile(id, name) => {
+                if let Err(e) = self.profiles.rename(id, &name) {
+                    println!("error renaming profile: {}", e);
+                    return false;
+                }
+                self.send_profiles();
+                if id != self.profile {
+                    return false;
+                }
+                self.collection.owner = name.trim().to_string();
+            }
+            DeleteProfile(id) => {
+                if id == self.profile {
+                    println!("error: the open profile can't be deleted");
+                    return false;
+                }
+                if let Err(e) = self.profiles.delete(id) {
+                    println!("error deleting profile: {}", e);
+                }
+                self.send_profiles();
+                return false;
+            }
+            SwitchProfile(id, passphrase) => {
+                if id == self.profile {
+                    return false;
+                }
+                let given = passphrase.is_some();
+                let opened = open_profile(
+                    self.kind.as_deref(),
+                    passphrase.as_deref().or(self.passphrase.as_deref()),
+                    &self.profiles,
+                    id,
+                );
+                let (storage, collection) = match opened {
+                    Ok(opened) => opened,
                     Err(e) => {
-                        println!("error adding card to deck id {}: {}", deck_id, e);
-                        return;
+                        println!("error opening profile: {}", e);
+                        let reply = if e.is::<WrongPassphrase>() {
+                            data::SendData::PassphraseNeeded(id, given)
+                        } else {
+                            data::SendData::Error(format!("Couldn't open the profile: {}", e))
+                        };
+                        if let Err(e) = self.tx.send(reply) {
+                            println!("error sending profile error to gui: {}", e);
+                        }
+                        return false;
                     }
+                };
+                // Changes earlier in the batch haven't been saved yet. Leave a
+                // backup of the collection being closed too, then start afresh
+                // on the other, whose history has nothing to do with it.
+                self.save();
+                self.back_up();
+                self.backups = Backups::new(self.profiles.dir_of(id).join(Backups::DIR));
+                self.backups.set_cipher(storage.cipher());
+                self.backups.set_format(storage.format());
+                self.storage = storage;
+                self.profile = id;
+                self.collection = collection;
+                self.history = History::new();
+                self.conflict = false;
+                self.unsaved = false;
+                if let Err(e) = self.profiles.set_last(id) {
+                    println!("error remembering last profile: {}", e);
                 }
+                self.send_profiles();
+                self.send_backups();
             }
-            DeleteCard(deck_id, card_id) => match self.collection.remove_card(deck_id, card_id) {
-                Ok(_) => {
-                    if let Err(e) = self.send_update() {
-                        println!("error sending post delete card update: {}", e);
-                    }
+            ReloadCollection => match self.storage.load() {
+                Ok(collection) => {
+                    self.collection = self.with_profile(collection);
+                    self.conflict = false;
                 }
                 Err(e) => {
-                    println!(
-                        "error deleting card id {} from deck id {}: {}",
-                        card_id, deck_id, e
-                    );
-                    return;
+                    println!("error reloading collection: {}", e);
+                    return false;
                 }
             },
-            EditCard(deck_id, card_id, question, answer) => {
-                match self
-                    .collection
-                    .edit_card(deck_id, card_id, question, answer)
-                {
-                    Ok(_) => {
-                        if let Err(e) = self.send_update() {
-                            println!("error sending post edit card update: {}", e);
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "error editing card id {} in deck id {}: {}",
-                            card_id, deck_id, e
-                        );
-                        return;
-                    }
+            // Saved along with every other change, over the outside one
+            KeepCollection => self.conflict = false,
+            CheckCollection => {
+                let report = self.collection.check();
+                let repaired = !report.repaired.is_empty();
+                if let Err(e) = self.tx.send(data::SendData::Check(report)) {
+                    println!("error sending collection check: {}", e);
+                }
+                if !repaired {
+                    return false;
                 }
             }
-            GetQuiz => {
-                let quiz = self.collection.get_quiz();
-                match self.tx.send(data::SendData::Quiz(quiz)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        println!("error sending quiz: {}", e);
-                    }
+            // Handled while listening, as it ends the service
+            Quit => return false,
+            // Snapshots keep the profile name they were taken under, which
+            // may have been renamed since
+            Undo => match self.history.undo(self.collection.clone()) {
+                Some(collection) => self.collection = self.with_profile(collection),
+                None => return false,
+            },
+            Redo => match self.history.redo(self.collection.clone()) {
+                Some(collection) => self.collection = self.with_profile(collection),
+                None => return false,
+            },
+        }
+        if let Some((label, collection)) = before {
+            self.history.record(label, collection);
+        }
+        true
+    }
+
+    // Replaces the collection with the stored one if something else changed
+    // it, returning whether it did. If there are unsaved changes here too the
+    // GUI is asked which to keep instead. Those come from a save that failed,
+    // including one refused for an outside change that couldn't be read yet.
+    fn take_external_changes(&mut self) -> bool {
+        let collection = match self.storage.load_external_changes() {
+            Ok(Some(collection)) => collection,
+            Ok(None) => return false,
+            Err(e) => {
+                println!("error reading outside changes to the collection: {}", e);
+                return false;
+            }
+        };
+        if self.unsaved {
+            // Already asked about an earlier outside change, and reloading
+            // will take up this one as well
+            if !self.conflict {
+                self.conflict = true;
+                if let Err(e) = self.tx.send(data::SendData::Conflict) {
+                    println!("error sending conflict: {}", e);
                 }
-                return;
             }
-            QuizResults(results) => {
-                if let Err(e) = self.collection.process_results(results) {
-                    println!("error while processing quiz result: {}", e);
+            return false;
+        }
+        println!("Reloaded the collection after it was changed outside of Ranki.");
+        let collection = self.with_profile(collection);
+        let before = mem::replace(&mut self.collection, collection);
+        self.history.record("Outside Change", before);
+        true
+    }
+
+    // Stored collections don't know which profile they were opened from
+    fn with_profile(&self, mut collection: data::Collection) -> data::Collection {
+        collection.id = self.profile;
+        collection.owner = match self.profiles.get(self.profile) {
+            Some(profile) => profile.name.clone(),
+            None => self.collection.owner.clone(),
+        };
+        collection
+    }
+
+    fn back_up(&mut self) {
+        if let Err(e) = self.backups.write(&self.collection) {
+            println!("error backing up collection: {}", e);
+        }
+    }
+
+    fn send_backups(&mut self) {
+        match self.backups.list() {
+            Ok(backups) => {
+                if let Err(e) = self.tx.send(data::SendData::Backups(backups)) {
+                    println!("error sending backups: {}", e);
                 }
             }
+            Err(e) => println!("error listing backups: {}", e),
+        }
+    }
+
+    fn send_profiles(&self) {
+        let profiles = self.profiles.list().to_vec();
+        if let Err(e) = self.tx.send(data::SendData::Profiles(profiles)) {
+            println!("error sending profiles: {}", e);
         }
-        self.save();
     }
 
-    fn send_update(&self) -> Result<(), mpsc::SendError<data::SendData>> {
-        self.tx
-            .send(data::SendData::Collection(self.collection.clone()))
+    fn send_history(&self) {
+        let (undo, redo) = self.history.labels();
+        if let Err(e) = self.tx.send(data::SendData::History(undo, redo)) {
+            println!("error sending undo history: {}", e);
+        }
     }
 
-    fn save(&self) {
-        if let Err(e) = self.collection.flush_to_file() {
-            println!("error saving collection to file: {}", e);
+    fn send_update(&self) {
+        let update = data::SendData::Collection(self.collection.clone());
+        if let Err(e) = self.tx.send(update) {
+            println!("error sending collection update: {}", e);
         }
     }
+
+    fn save(&mut self) {
+        // Held back until the GUI says which collection to keep
+        if self.conflict {
+            self.unsaved = true;
+            return;
+        }
+        match self.storage.save(&self.collection) {
+            Ok(()) => self.unsaved = false,
+            Err(e) => {
+                println!("error saving collection to file: {}", e);
+                self.unsaved = true;
+            }
+        }
+    }
+}
+
+// Loads the collection of a profile, which carries the profile's id and name
+fn open_profile(
+    kind: Option<&str>,
+    passphrase: Option<&str>,
+    profiles: &Profiles,
+    id: u32,
+) -> Result<(Box<dyn Storage>, data::Collection), Box<dyn Error>> {
+    let profile = match profiles.get(id) {
+        Some(profile) => profile,
+        None => return Err(format!("no profile with id {}", id).into()),
+    };
+    let mut storage = storage::open(kind, &profiles.dir_of(id), passphrase)?;
+    let mut collection = storage.load()?;
+    if collection.purge_expired_trash() {
+        println!("Purged expired items from the trash.");
+    }
+    collection.id = profile.id;
+    collection.owner = profile.name.clone();
+    Ok((storage, collection))
 }
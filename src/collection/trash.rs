@@ -0,0 +1,154 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::data::{Card, Collection, Deck};
+
+/// Decks and cards that have been deleted, kept with their full scheduling
+/// history until they're restored or purged
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Trash {
+    pub decks: Vec<TrashedDeck>,
+    pub cards: Vec<TrashedCard>,
+    /// Number of days deleted items are kept before being purged for good
+    pub retention_days: u32,
+}
+
+impl Default for Trash {
+    fn default() -> Self {
+        Trash {
+            decks: Vec::new(),
+            cards: Vec::new(),
+            retention_days: 30,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TrashedDeck {
+    pub deck: Deck,
+    pub deleted: SystemTime,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct TrashedCard {
+    pub deck_id: u32,
+    pub deck_title: String,
+    pub card: Card,
+    pub deleted: SystemTime,
+}
+
+impl Collection {
+    /// Moves a deck and all of its cards into the trash
+    pub fn trash_deck(&mut self, id: u32) -> Result<(), String> {
+        match self.remove_deck_by_id(id) {
+            Some(deck) => {
+                self.trash_mut().decks.push(TrashedDeck {
+                    deck,
+                    deleted: SystemTime::now(),
+                });
+                Ok(())
+            }
+            None => Err(format!("could not find deck by id {}", id)),
+        }
+    }
+
+    /// Moves a card into the trash. Cards borrowed by a filtered deck are
+    /// trashed as belonging to their home deck.
+    pub fn trash_card(&mut self, uid: Uuid) -> Result<(), String> {
+        let (deck_id, mut card) = self.remove_card(uid)?;
+        let deck_id = card.leave_filtered_deck().unwrap_or(deck_id);
+        let deck_title = match self.deck_pos_by_id(deck_id) {
+            Some(pos) => self.decks[pos].title.clone(),
+            None => "".to_string(),
+        };
+        self.trash_mut().cards.push(TrashedCard {
+            deck_id,
+            deck_title,
+            card,
+            deleted: SystemTime::now(),
+        });
+        Ok(())
+    }
+
+    pub fn restore_deck(&mut self, id: u32) -> Result<(), String> {
+        match self.trash.decks.iter().position(|t| t.deck.id == id) {
+            Some(pos) => {
+                let trashed = self.trash_mut().decks.remove(pos);
+                self.push_deck(trashed.deck);
+                Ok(())
+            }
+            None => Err(format!("could not find deck by id {} in trash", id)),
+        }
+    }
+
+    /// Puts a card back into the deck it was deleted from, which has to exist
+    pub fn restore_card(&mut self, uid: Uuid) -> Result<(), String> {
+        let pos = match self.trashed_card_pos(uid) {
+            Some(pos) => pos,
+            None => return Err(format!("could not find card by uid {} in trash", uid)),
+        };
+        let deck_pos = match self.deck_pos_by_id(self.trash.cards[pos].deck_id) {
+            Some(deck_pos) => deck_pos,
+            None => {
+                return Err(format!(
+                    "deck \"{}\" is gone, restore it before its cards",
+                    self.trash.cards[pos].deck_title
+                ))
+            }
+        };
+        let trashed = self.trash_mut().cards.remove(pos);
+        self.deck_mut(deck_pos).insert_card(trashed.card);
+        Ok(())
+    }
+
+    pub fn purge_deck(&mut self, id: u32) -> Result<(), String> {
+        match self.trash.decks.iter().position(|t| t.deck.id == id) {
+            Some(pos) => {
+                self.trash_mut().decks.remove(pos);
+                Ok(())
+            }
+            None => Err(format!("could not find deck by id {} in trash", id)),
+        }
+    }
+
+    pub fn purge_card(&mut self, uid: Uuid) -> Result<(), String> {
+        match self.trashed_card_pos(uid) {
+            Some(pos) => {
+                self.trash_mut().cards.remove(pos);
+                Ok(())
+            }
+            None => Err(format!("could not find card by uid {} in trash", uid)),
+        }
+    }
+
+    pub fn empty_trash(&mut self) {
+        let trash = self.trash_mut();
+        trash.decks.clear();
+        trash.cards.clear();
+    }
+
+    /// Purges everything that has been in the trash for longer than the
+    /// retention period, returning whether anything was purged
+    pub fn purge_expired_trash(&mut self) -> bool {
+        let retention = Duration::from_secs(self.trash.retention_days as u64 * 86400);
+        let now = SystemTime::now();
+        let kept = |deleted: &SystemTime| *deleted + retention > now;
+        // Leave the trash shared with other copies of the collection unless
+        // something in it has actually expired
+        if self.trash.decks.iter().all(|t| kept(&t.deleted))
+            && self.trash.cards.iter().all(|t| kept(&t.deleted))
+        {
+            return false;
+        }
+        let trash = self.trash_mut();
+        trash.decks.retain(|t| kept(&t.deleted));
+        trash.cards.retain(|t| kept(&t.deleted));
+        true
+    }
+
+    fn trashed_card_pos(&self, uid: Uuid) -> Option<usize> {
+        self.trash.cards.iter().position(|t| t.card.uid == uid)
+    }
+}
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::*;
+
+use relm::{EventStream, Widget};
+use relm_derive::{widget, Msg};
+
+use super::card_browser::{format_datetime, secs_since_epoch};
+use super::window;
+use crate::collection::{BackupInfo, BackupSettings, Collection};
+
+// Columns of the backing list store. The first three are displayed, the rest
+// hold raw values used for sorting and for identifying the selected backup.
+const TAKEN_COL: u32 = 0;
+const DECKS_COL: u32 = 1;
+const CARDS_COL: u32 = 2;
+const TAKEN_SECS_COL: u32 = 3;
+const NAME_COL: u32 = 4;
+
+// (title, displayed column, column to sort by)
+const DISPLAYED_COLUMNS: [(&str, u32, u32); 3] = [
+    ("Taken", TAKEN_COL, TAKEN_SECS_COL),
+    ("Decks", DECKS_COL, DECKS_COL),
+    ("Cards", CARDS_COL, CARDS_COL),
+];
+
+// ----- Backups Window -----
+// A separate window listing the backups of the collection, any of which can
+// replace the live collection after checking what it holds
+pub struct BackupViewModel {
+    backups: Vec<BackupInfo>,
+    collection: Rc<RefCell<Collection>>,
+    parent_stream: EventStream<window::Msg>,
+    store: gtk::ListStore,
+}
+
+#[derive(Msg)]
+pub enum BackupMsg {
+    BackUp,
+    Close,
+    Listed(Vec<BackupInfo>),
+    Refresh,
+    Restore,
+    SelectionChange,
+    SetSettings,
+}
+
+#[widget]
+impl Widget for BackupView {
+    fn model(
+        (collection, parent_stream): (Rc<RefCell<Collection>>, EventStream<window::Msg>),
+    ) -> BackupViewModel {
+        let store = gtk::ListStore::new(&[
+            gtk::Type::String,
+            gtk::Type::U32,
+            gtk::Type::U32,
+            gtk::Type::U64,
+            gtk::Type::String,
+        ]);
+        BackupViewModel {
+            backups: vec![],
+            collection,
+            parent_stream,
+            store,
+        }
+    }
+
+    fn init_view(&mut self) {
+        self.tree.set_model(Some(&self.model.store));
+        for (title, display_col, sort_col) in DISPLAYED_COLUMNS.iter() {
+            let cell = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", *display_col as i32);
+            column.set_sort_column_id(*sort_col as i32);
+            self.tree.append_column(&column);
+        }
+        self.keep.set_range(1.0, 1000.0);
+        self.keep.set_increments(1.0, 10.0);
+        self.interval.set_range(1.0, 1440.0);
+        self.interval.set_increments(1.0, 30.0);
+        self.show_settings();
+    }
+
+    fn update(&mut self, event: BackupMsg) {
+        match event {
+            BackupMsg::BackUp => {
+                self.model.parent_stream.emit(window::Msg::BackUp);
+            }
+            BackupMsg::Close => {
+                self.model.parent_stream.emit(window::Msg::CloseBackups);
+            }
+            BackupMsg::Listed(backups) => {
+                self.model.store.clear();
+                for backup in &backups {
+                    self.model.store.insert_with_values(
+                        None,
+                        &[TAKEN_COL, DECKS_COL, CARDS_COL, TAKEN_SECS_COL, NAME_COL],
+                        &[
+                            &format_datetime(backup.taken),
+                            &(backup.decks as u32),
+                            &(backup.cards as u32),
+                            &secs_since_epoch(backup.taken),
+                            &backup.name,
+                        ],
+                    );
+                }
+                self.model.backups = backups;
+                self.preview.set_text("");
+            }
+            BackupMsg::Refresh => {
+                self.show_settings();
+            }
+            BackupMsg::Restore => match self.selected() {
+                Some(backup) => {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::RestoreBackup(backup.name.clone()));
+                }
+                None => self.preview.set_text("Select a backup to restore first"),
+            },
+            BackupMsg::SelectionChange => {
+                let backup = match self.selected() {
+                    Some(backup) => backup,
+                    None => return,
+                };
+                let (decks, cards) = match self.model.collection.try_borrow() {
+                    Ok(c) => (
+                        c.decks.len(),
+                        c.decks.iter().map(|deck| deck.cards.len()).sum::<usize>(),
+                    ),
+                    Err(_) => return,
+                };
+                self.preview.set_text(&format!(
+                    "Restoring replaces the current collection ({} decks, {} cards) \
+                     with the backup from {} ({} decks, {} cards)",
+                    decks,
+                    cards,
+                    format_datetime(backup.taken),
+                    backup.decks,
+                    backup.cards
+                ));
+            }
+            BackupMsg::SetSettings => {
+                let current = match self.model.collection.try_borrow() {
+                    Ok(c) => c.backup_settings.clone(),
+                    Err(_) => return,
+                };
+                let settings = BackupSettings {
+                    keep: self.keep.get_value_as_int().max(1) as usize,
+                    interval_minutes: self.interval.get_value_as_int().max(1) as u32,
+                    ..current
+                };
+                self.model
+                    .parent_stream
+                    .emit(window::Msg::SetBackupSettings(settings));
+            }
+        }
+    }
+
+    fn show_settings(&self) {
+        if let Ok(c) = self.model.collection.try_borrow() {
+            self.keep.set_value(c.backup_settings.keep as f64);
+            self.interval
+                .set_value(c.backup_settings.interval_minutes as f64);
+        }
+    }
+
+    fn selected(&self) -> Option<&BackupInfo> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        let name = model
+            .get_value(&iter, NAME_COL as i32)
+            .get::<String>()
+            .ok()??;
+        self.model.backups.iter().find(|backup| backup.name == name)
+    }
+
+    view! {
+        gtk::Window {
+            title: "Backups",
+            border_width: 10,
+            default_width: 500,
+            default_height: 400,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                gtk::ScrolledWindow {
+                    child: {
+                        expand: true,
+                    },
+                    shadow_type: gtk::ShadowType::Out,
+                    #[name="tree"]
+                    gtk::TreeView {
+                        cursor_changed => BackupMsg::SelectionChange,
+                    },
+                },
+                #[name="preview"]
+                gtk::Label {
+                    line_wrap: true,
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Button {
+                        label: "Restore Selected Backup",
+                        clicked => BackupMsg::Restore,
+                    },
+                    gtk::Button {
+                        label: "Back Up Now",
+                        clicked => BackupMsg::BackUp,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Label {
+                        label: "Recent backups to keep",
+                    },
+                    #[name="keep"]
+                    gtk::SpinButton {},
+                    gtk::Label {
+                        label: "Minutes between backups",
+                    },
+                    #[name="interval"]
+                    gtk::SpinButton {},
+                    gtk::Button {
+                        label: "Set",
+                        clicked => BackupMsg::SetSettings,
+                    },
+                },
+            },
+            delete_event(_, _) => (BackupMsg::Close, Inhibit(false)),
+        }
+    }
+}
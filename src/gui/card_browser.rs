@@ -0,0 +1,592 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use gtk::*;
+
+use relm::{EventStream, Widget};
+use relm_derive::{widget, Msg};
+use uuid::Uuid;
+
+use super::window;
+use crate::collection::{Collection, Query, Replace};
+
+// Columns of the backing list store. The first ten are displayed, the rest
+// hold raw values used for sorting and for identifying the selected card.
+const DECK_COL: u32 = 0;
+const QUESTION_COL: u32 = 1;
+const ANSWER_COL: u32 = 2;
+const STAGE_COL: u32 = 3;
+const INTERVAL_COL: u32 = 4;
+const DUE_COL: u32 = 5;
+const LAPSES_COL: u32 = 6;
+const TAGS_COL: u32 = 7;
+const CREATED_COL: u32 = 8;
+const MODIFIED_COL: u32 = 9;
+const INTERVAL_SECS_COL: u32 = 10;
+const DUE_SECS_COL: u32 = 11;
+const CREATED_SECS_COL: u32 = 12;
+const MODIFIED_SECS_COL: u32 = 13;
+const CARD_UID_COL: u32 = 14;
+
+// (title, displayed column, column to sort by)
+const DISPLAYED_COLUMNS: [(&str, u32, u32); 10] = [
+    ("Deck", DECK_COL, DECK_COL),
+    ("Question", QUESTION_COL, QUESTION_COL),
+    ("Answer", ANSWER_COL, ANSWER_COL),
+    ("Stage", STAGE_COL, STAGE_COL),
+    ("Interval", INTERVAL_COL, INTERVAL_SECS_COL),
+    ("Due", DUE_COL, DUE_SECS_COL),
+    ("Lapses", LAPSES_COL, LAPSES_COL),
+    ("Tags", TAGS_COL, TAGS_COL),
+    ("Created", CREATED_COL, CREATED_SECS_COL),
+    ("Modified", MODIFIED_COL, MODIFIED_SECS_COL),
+];
+
+// ----- Card Browser Window -----
+// A separate window listing the cards of every deck in a sortable table,
+// narrowed down by a search query
+pub struct CardBrowserModel {
+    collection: Rc<RefCell<Collection>>,
+    duplicates_only: bool,
+    /// Uid of the card whose edit history is being shown
+    history_card: Option<Uuid>,
+    parent_stream: EventStream<window::Msg>,
+    query: Query,
+    query_text: String,
+    store: gtk::ListStore,
+}
+
+#[derive(Msg)]
+pub enum CardBrowserMsg {
+    Close,
+    MergeDuplicates,
+    RevertCard,
+    RevisionChange,
+    ShowHistory,
+    PreviewReplace,
+    QueryChange,
+    Refresh,
+    ReplaceAll,
+    SetTags,
+    ToggleDuplicates,
+}
+
+#[widget]
+impl Widget for CardBrowser {
+    fn model(
+        (collection, parent_stream): (Rc<RefCell<Collection>>, EventStream<window::Msg>),
+    ) -> CardBrowserModel {
+        let store = gtk::ListStore::new(&[
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::U32,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::U64,
+            gtk::Type::U64,
+            gtk::Type::U64,
+            gtk::Type::U64,
+            gtk::Type::String,
+        ]);
+        CardBrowserModel {
+            collection,
+            duplicates_only: false,
+            history_card: None,
+            parent_stream,
+            query: Query::default(),
+            query_text: String::new(),
+            store,
+        }
+    }
+
+    fn init_view(&mut self) {
+        self.tree.set_model(Some(&self.model.store));
+        for (title, display_col, sort_col) in DISPLAYED_COLUMNS.iter() {
+            let cell = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", *display_col as i32);
+            column.set_sort_column_id(*sort_col as i32);
+            self.tree.append_column(&column);
+        }
+        self.populate();
+    }
+
+    fn update(&mut self, event: CardBrowserMsg) {
+        match event {
+            CardBrowserMsg::Close => {
+                self.model.parent_stream.emit(window::Msg::CloseBrowser);
+            }
+            CardBrowserMsg::MergeDuplicates => {
+                let selected = match self.selected_card() {
+                    Some(uid) => uid,
+                    None => {
+                        self.alert.set_text("Select one of the duplicates to merge first");
+                        return;
+                    }
+                };
+                let group = match self.model.collection.try_borrow() {
+                    Ok(c) => c
+                        .find_duplicates()
+                        .into_iter()
+                        .find(|group| group.contains(&selected)),
+                    Err(_) => return,
+                };
+                match group {
+                    Some(group) => {
+                        self.model
+                            .parent_stream
+                            .emit(window::Msg::MergeCards(group));
+                    }
+                    None => {
+                        self.alert.set_text("The selected card has no duplicates");
+                    }
+                }
+            }
+            CardBrowserMsg::QueryChange => {
+                let text = match self.query.get_text() {
+                    None => {
+                        println!("error: failed to get text on browser query change");
+                        return;
+                    }
+                    Some(string) => string,
+                };
+                match Query::parse(&text) {
+                    Ok(query) => {
+                        self.alert.set_text("");
+                        self.model.query = query;
+                        self.model.query_text = text.to_string();
+                        self.populate();
+                    }
+                    Err(e) => {
+                        self.alert.set_text(&e);
+                    }
+                }
+            }
+            CardBrowserMsg::PreviewReplace => {
+                let replace = match self.replace_request() {
+                    Some(replace) => replace,
+                    None => return,
+                };
+                let preview = match self.model.collection.try_borrow() {
+                    Ok(c) => c.preview_replace(&replace),
+                    Err(_) => return,
+                };
+                match preview {
+                    Ok(changes) => {
+                        let mut lines = vec![format!("{} cards would change", changes.len())];
+                        for change in changes {
+                            let fields = [
+                                ("question", &change.before.0, &change.after.0),
+                                ("answer", &change.before.1, &change.after.1),
+                                ("note", &change.before.2, &change.after.2),
+                            ];
+                            for (field, before, after) in fields.iter() {
+                                if before != after {
+                                    lines.push(format!(
+                                        "card {} {}: {} -> {}",
+                                        change.uid, field, before, after
+                                    ));
+                                }
+                            }
+                        }
+                        self.replace_preview.set_text(&lines.join("\n"));
+                    }
+                    Err(e) => self.alert.set_text(&e),
+                }
+            }
+            CardBrowserMsg::Refresh => {
+                self.populate();
+                self.show_history();
+            }
+            CardBrowserMsg::RevertCard => {
+                let revision = self
+                    .revisions
+                    .get_active_id()
+                    .and_then(|id| id.as_str().parse::<usize>().ok());
+                match (self.model.history_card, revision) {
+                    (Some(uid), Some(revision)) => {
+                        self.model
+                            .parent_stream
+                            .emit(window::Msg::RevertCard(uid, revision));
+                    }
+                    _ => self.alert.set_text("Select a revision to revert to first"),
+                }
+            }
+            CardBrowserMsg::RevisionChange => {
+                let revision = self
+                    .revisions
+                    .get_active_id()
+                    .and_then(|id| id.as_str().parse::<usize>().ok());
+                let (uid, revision) = match (self.model.history_card, revision) {
+                    (Some(uid), Some(revision)) => (uid, revision),
+                    _ => {
+                        self.revision_content.set_text("");
+                        return;
+                    }
+                };
+                let text = match self.model.collection.try_borrow() {
+                    Ok(c) => c
+                        .card_by_uid(uid)
+                        .and_then(|(_, card)| card.revisions().get(revision))
+                        .map(|old| {
+                            format!(
+                                "Question: {}\nAnswer: {}\nNote: {}",
+                                old.question, old.answer, old.note
+                            )
+                        }),
+                    Err(_) => return,
+                };
+                self.revision_content.set_text(&text.unwrap_or_default());
+            }
+            CardBrowserMsg::ShowHistory => match self.selected_card() {
+                Some(uid) => {
+                    self.model.history_card = Some(uid);
+                    self.show_history();
+                }
+                None => self.alert.set_text("Select a card to show the history of first"),
+            },
+            CardBrowserMsg::ReplaceAll => {
+                if let Some(replace) = self.replace_request() {
+                    self.replace_preview.set_text("");
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::FindReplace(replace));
+                }
+            }
+            CardBrowserMsg::SetTags => {
+                let uid = match self.selected_card() {
+                    Some(uid) => uid,
+                    None => {
+                        self.alert.set_text("Select a card to tag first");
+                        return;
+                    }
+                };
+                let text = match self.tags.get_text() {
+                    None => {
+                        println!("error: failed to get text from browser tags field");
+                        return;
+                    }
+                    Some(string) => string,
+                };
+                let tags = text.split_whitespace().map(|t| t.to_string()).collect();
+                self.model
+                    .parent_stream
+                    .emit(window::Msg::SetTags(uid, tags));
+                self.tags.set_text("");
+            }
+            CardBrowserMsg::ToggleDuplicates => {
+                self.model.duplicates_only = self.duplicates_toggle.get_active();
+                self.populate();
+            }
+        }
+    }
+
+    // Rebuild the rows of the table from the current collection and query
+    fn populate(&mut self) {
+        self.model.store.clear();
+        let c = match self.model.collection.try_borrow() {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+        let mut found = c.search(&self.model.query);
+        if self.model.duplicates_only {
+            let duplicates: Vec<Uuid> = c.find_duplicates().into_iter().flatten().collect();
+            found.retain(|(_, card)| duplicates.contains(&card.uid));
+        }
+        self.count_label
+            .set_text(&format!("{} cards shown", found.len()));
+        for (deck, card) in found {
+            let due = card.due();
+            let (created, created_secs) = format_optional_time(card.created());
+            let (modified, modified_secs) = format_optional_time(card.modified());
+            self.model.store.insert_with_values(
+                None,
+                &[
+                    DECK_COL,
+                    QUESTION_COL,
+                    ANSWER_COL,
+                    STAGE_COL,
+                    INTERVAL_COL,
+                    DUE_COL,
+                    LAPSES_COL,
+                    TAGS_COL,
+                    CREATED_COL,
+                    MODIFIED_COL,
+                    INTERVAL_SECS_COL,
+                    DUE_SECS_COL,
+                    CREATED_SECS_COL,
+                    MODIFIED_SECS_COL,
+                    CARD_UID_COL,
+                ],
+                &[
+                    &deck.title,
+                    &card.question,
+                    &card.answer,
+                    &card.stage_name(),
+                    &format_interval(card.interval()),
+                    &format_date(due),
+                    &card.lapses(),
+                    &card.tags.join(" "),
+                    &created,
+                    &modified,
+                    &card.interval().as_secs(),
+                    &secs_since_epoch(due),
+                    &created_secs,
+                    &modified_secs,
+                    &card.uid.to_string(),
+                ],
+            );
+        }
+    }
+
+    // Lists the earlier revisions of the card picked for showing its history,
+    // newest first
+    fn show_history(&self) {
+        self.revisions.remove_all();
+        self.revision_content.set_text("");
+        let uid = match self.model.history_card {
+            Some(uid) => uid,
+            None => return,
+        };
+        let c = match self.model.collection.try_borrow() {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+        let card = match c.card_by_uid(uid) {
+            Some((_, card)) => card,
+            None => {
+                self.history_label.set_text("The card is no longer in the collection");
+                return;
+            }
+        };
+        self.history_label.set_text(&format!(
+            "History of card {}: {} earlier revisions",
+            card.uid,
+            card.revisions().len()
+        ));
+        for (idx, old) in card.revisions().iter().enumerate().rev() {
+            self.revisions.append(
+                Some(&idx.to_string()),
+                &format!("Replaced {}", format_datetime(old.replaced)),
+            );
+        }
+    }
+
+    // Gathers up the find-and-replace fields, scoped to the current search
+    fn replace_request(&self) -> Option<Replace> {
+        let (find, replacement) = match (self.find.get_text(), self.replacement.get_text()) {
+            (Some(find), Some(replacement)) => (find.to_string(), replacement.to_string()),
+            _ => {
+                println!("error: failed to get text from find and replace fields");
+                return None;
+            }
+        };
+        if find.is_empty() {
+            self.alert.set_text("Enter some text to find first");
+            return None;
+        }
+        Some(Replace {
+            find,
+            replacement,
+            regex: self.replace_regex.get_active(),
+            query: self.model.query_text.clone(),
+            questions: self.replace_questions.get_active(),
+            answers: self.replace_answers.get_active(),
+            notes: self.replace_notes.get_active(),
+        })
+    }
+
+    fn selected_card(&self) -> Option<Uuid> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        let uid = model
+            .get_value(&iter, CARD_UID_COL as i32)
+            .get::<String>()
+            .ok()??;
+        uid.parse().ok()
+    }
+
+    view! {
+        gtk::Window {
+            title: "Card Browser",
+            border_width: 10,
+            default_width: 900,
+            default_height: 500,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                #[name="query"]
+                gtk::Entry {
+                    placeholder_text: Some("Search, e.g. deck:Spanish tag:verbs is:due"),
+                    changed => CardBrowserMsg::QueryChange,
+                },
+                #[name="alert"]
+                gtk::Label {},
+                #[name="count_label"]
+                gtk::Label {},
+                gtk::ScrolledWindow {
+                    child: {
+                        expand: true,
+                    },
+                    shadow_type: gtk::ShadowType::Out,
+                    #[name="tree"]
+                    gtk::TreeView {},
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="tags"]
+                    gtk::Entry {
+                        placeholder_text: Some("Space separated tags"),
+                    },
+                    gtk::Button {
+                        label: "Set Tags on Selected Card",
+                        clicked => CardBrowserMsg::SetTags,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="duplicates_toggle"]
+                    gtk::CheckButton {
+                        label: "Only show duplicates",
+                        toggled => CardBrowserMsg::ToggleDuplicates,
+                    },
+                    gtk::Button {
+                        label: "Merge Duplicates of Selected Card",
+                        clicked => CardBrowserMsg::MergeDuplicates,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Button {
+                        label: "Show History of Selected Card",
+                        clicked => CardBrowserMsg::ShowHistory,
+                    },
+                    #[name="history_label"]
+                    gtk::Label {
+                        selectable: true,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="revisions"]
+                    gtk::ComboBoxText {
+                        changed => CardBrowserMsg::RevisionChange,
+                    },
+                    gtk::Button {
+                        label: "Revert to Revision",
+                        clicked => CardBrowserMsg::RevertCard,
+                    },
+                },
+                #[name="revision_content"]
+                gtk::Label {
+                    selectable: true,
+                    xalign: 0.0,
+                },
+                gtk::Label {
+                    label: "Find and replace in the cards matching the search",
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="find"]
+                    gtk::Entry {
+                        placeholder_text: Some("Find"),
+                    },
+                    #[name="replacement"]
+                    gtk::Entry {
+                        placeholder_text: Some("Replace with"),
+                    },
+                    #[name="replace_regex"]
+                    gtk::CheckButton {
+                        label: "Regular expression",
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="replace_questions"]
+                    gtk::CheckButton {
+                        label: "Questions",
+                        active: true,
+                    },
+                    #[name="replace_answers"]
+                    gtk::CheckButton {
+                        label: "Answers",
+                        active: true,
+                    },
+                    #[name="replace_notes"]
+                    gtk::CheckButton {
+                        label: "Notes",
+                        active: true,
+                    },
+                    gtk::Button {
+                        label: "Preview",
+                        clicked => CardBrowserMsg::PreviewReplace,
+                    },
+                    gtk::Button {
+                        label: "Replace All",
+                        clicked => CardBrowserMsg::ReplaceAll,
+                    },
+                },
+                gtk::ScrolledWindow {
+                    min_content_height: 100,
+                    #[name="replace_preview"]
+                    gtk::Label {
+                        selectable: true,
+                        xalign: 0.0,
+                    },
+                },
+            },
+            delete_event(_, _) => (CardBrowserMsg::Close, Inhibit(false)),
+        }
+    }
+}
+
+pub(super) fn secs_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_secs()
+}
+
+fn format_interval(interval: Duration) -> String {
+    let days = interval.as_secs() / 86400;
+    if days == 0 {
+        "-".to_string()
+    } else {
+        format!("{}d", days)
+    }
+}
+
+// Display text and sort key for timestamps that may not have been recorded
+fn format_optional_time(time: Option<SystemTime>) -> (String, u64) {
+    match time {
+        Some(time) => (format_datetime(time), secs_since_epoch(time)),
+        None => ("-".to_string(), 0),
+    }
+}
+
+/// Formats a point in time as a UTC date and time (YYYY-MM-DD HH:MM)
+pub(super) fn format_datetime(time: SystemTime) -> String {
+    let secs = secs_since_epoch(time) % 86400;
+    format!("{} {:02}:{:02}", format_date(time), secs / 3600, secs % 3600 / 60)
+}
+
+/// Formats a point in time as a UTC calendar date (YYYY-MM-DD). The conversion
+/// from days since the epoch follows Howard Hinnant's `civil_from_days`.
+pub(super) fn format_date(time: SystemTime) -> String {
+    let days = (secs_since_epoch(time) / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -14,18 +14,21 @@ enum ModalState {
 pub struct CardOpModel {
     modal_state: Option<ModalState>,
     parent_stream: EventStream<CardViewMsg>,
+    // Question, answer and note of the selected card, to fill in when editing
+    selected: Option<(String, String, String)>,
     stream: EventStream<CardOpMsg>,
 }
 
 #[derive(Msg)]
 pub enum CardOpMsg {
-    Add(String, String),
+    Add(String, String, String),
     AddModal,
     Delete,
     DeleteModal,
-    Edit(String, String),
+    Edit(String, String, String),
     EditModal,
     Cancel,
+    Selected(Option<(String, String, String)>),
 }
 
 #[widget]
@@ -34,18 +37,25 @@ impl Widget for CardOpGrid {
         CardOpModel {
             modal_state: None,
             parent_stream,
+            selected: None,
             stream: relm.stream().clone(),
         }
     }
 
     fn update(&mut self, event: CardOpMsg) {
+        // Changing selection shouldn't close whichever modal is open
+        if let CardOpMsg::Selected(selected) = event {
+            self.model.selected = selected;
+            return;
+        }
+
         self.remove_modal();
 
         match event {
-            CardOpMsg::Add(question, answer) => {
+            CardOpMsg::Add(question, answer, note) => {
                 self.model
                     .parent_stream
-                    .emit(CardViewMsg::Add(question, answer));
+                    .emit(CardViewMsg::Add(question, answer, note));
             }
             CardOpMsg::AddModal => {
                 let widget = self
@@ -66,17 +76,19 @@ impl Widget for CardOpGrid {
                     .add_widget::<DeleteCardWidget>(self.model.stream.clone());
                 self.model.modal_state = Some(ModalState::Delete(widget));
             }
-            CardOpMsg::Edit(question, answer) => {
+            CardOpMsg::Edit(question, answer, note) => {
                 self.model
                     .parent_stream
-                    .emit(CardViewMsg::Edit(question, answer));
+                    .emit(CardViewMsg::Edit(question, answer, note));
             }
             CardOpMsg::EditModal => {
-                let widget = self
-                    .mod_box
-                    .add_widget::<EditCardWidget>(self.model.stream.clone());
+                let widget = self.mod_box.add_widget::<EditCardWidget>((
+                    self.model.stream.clone(),
+                    self.model.selected.clone(),
+                ));
                 self.model.modal_state = Some(ModalState::Edit(widget));
             }
+            CardOpMsg::Selected(_) => {}
         }
     }
 
@@ -144,6 +156,7 @@ impl Widget for CardOpGrid {
 pub struct AddCardModel {
     question: String,
     answer: String,
+    note: String,
     parent_stream: EventStream<CardOpMsg>,
 }
 
@@ -152,6 +165,7 @@ pub enum AddCardMsg {
     Add,
     QChange,
     AChange,
+    NChange,
 }
 
 #[widget]
@@ -160,6 +174,7 @@ impl Widget for AddCardWidget {
         AddCardModel {
             question: "".to_string(),
             answer: "".to_string(),
+            note: "".to_string(),
             parent_stream,
         }
     }
@@ -174,10 +189,12 @@ impl Widget for AddCardWidget {
                 self.model.parent_stream.emit(CardOpMsg::Add(
                     self.model.question.clone(),
                     self.model.answer.clone(),
+                    self.model.note.clone(),
                 ));
                 // Clear entry fields
                 self.question.set_text("");
                 self.answer.set_text("");
+                self.note.set_text("");
             }
             AddCardMsg::QChange => {
                 let text = match self.question.get_text() {
@@ -199,6 +216,16 @@ impl Widget for AddCardWidget {
                 };
                 self.model.answer = text.chars().collect();
             }
+            AddCardMsg::NChange => {
+                let text = match self.note.get_text() {
+                    None => {
+                        println!("error: failed to get text on add card note change");
+                        return;
+                    }
+                    Some(string) => string,
+                };
+                self.model.note = text.chars().collect();
+            }
         }
     }
 
@@ -216,6 +243,11 @@ impl Widget for AddCardWidget {
             gtk::Entry {
                 changed => AddCardMsg::AChange,
             },
+            #[name="note"]
+            gtk::Entry {
+                placeholder_text: Some("Note (optional)"),
+                changed => AddCardMsg::NChange,
+            },
             gtk::Button {
                 label: "Add",
                 clicked => AddCardMsg::Add,
@@ -266,6 +298,7 @@ impl Widget for DeleteCardWidget {
 pub struct EditCardModel {
     question: String,
     answer: String,
+    note: String,
     parent_stream: EventStream<CardOpMsg>,
 }
 
@@ -274,18 +307,30 @@ pub enum EditCardMsg {
     Edit,
     QChange,
     AChange,
+    NChange,
 }
 
 #[widget]
 impl Widget for EditCardWidget {
-    fn model(parent_stream: EventStream<CardOpMsg>) -> EditCardModel {
+    fn model(
+        (parent_stream, selected): (EventStream<CardOpMsg>, Option<(String, String, String)>),
+    ) -> EditCardModel {
+        let (question, answer, note) = selected.unwrap_or_default();
         EditCardModel {
-            question: "".to_string(),
-            answer: "".to_string(),
+            question,
+            answer,
+            note,
             parent_stream,
         }
     }
 
+    fn init_view(&mut self) {
+        // Start out from the selected card's content
+        self.question.set_text(&self.model.question);
+        self.answer.set_text(&self.model.answer);
+        self.note.set_text(&self.model.note);
+    }
+
     fn update(&mut self, event: EditCardMsg) {
         match event {
             EditCardMsg::Edit => {
@@ -296,10 +341,12 @@ impl Widget for EditCardWidget {
                 self.model.parent_stream.emit(CardOpMsg::Edit(
                     self.model.question.clone(),
                     self.model.answer.clone(),
+                    self.model.note.clone(),
                 ));
                 // Clear entry fields
                 self.question.set_text("");
                 self.answer.set_text("");
+                self.note.set_text("");
             }
             EditCardMsg::QChange => {
                 let text = match self.question.get_text() {
@@ -321,6 +368,16 @@ impl Widget for EditCardWidget {
                 };
                 self.model.answer = text.chars().collect();
             }
+            EditCardMsg::NChange => {
+                let text = match self.note.get_text() {
+                    None => {
+                        println!("error: failed to get text on edit card note change");
+                        return;
+                    }
+                    Some(string) => string,
+                };
+                self.model.note = text.chars().collect();
+            }
         }
     }
 
@@ -338,6 +395,11 @@ impl Widget for EditCardWidget {
             gtk::Entry {
                 changed => EditCardMsg::AChange,
             },
+            #[name="note"]
+            gtk::Entry {
+                placeholder_text: Some("Note (optional)"),
+                changed => EditCardMsg::NChange,
+            },
             gtk::Button {
                 label: "Edit",
                 clicked => EditCardMsg::Edit,
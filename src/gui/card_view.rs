@@ -6,26 +6,29 @@ use gtk::*;
 
 use relm::{Component, ContainerWidget, EventStream, Relm, Widget};
 use relm_derive::{widget, Msg};
+use uuid::Uuid;
 
-use super::card_operations::CardOpGrid;
+use super::card_operations::{CardOpGrid, CardOpMsg};
 use super::window;
-use crate::collection::Collection;
+use crate::collection::{Collection, Flag, Query};
 
 pub struct CardViewModel {
-    active_card: Option<u32>,
-    card_map: HashMap<u32, Component<CardWidget>>,
+    active_card: Option<Uuid>,
+    card_map: HashMap<Uuid, Component<CardWidget>>,
     collection: Rc<RefCell<Collection>>,
+    deck_id: Option<u32>,
     parent_stream: EventStream<window::Msg>,
     stream: EventStream<CardViewMsg>,
 }
 
 #[derive(Msg)]
 pub enum CardViewMsg {
-    Add(String, String),
+    Add(String, String, String),
     Delete,
-    Edit(String, String),
-    Selected(u32),
+    Edit(String, String, String),
+    Selected(Uuid),
     Cleared,
+    FilterChange,
     UpdateToDeck(u32),
 }
 
@@ -39,43 +42,83 @@ impl Widget for CardView {
             active_card: None,
             card_map: HashMap::new(),
             collection,
+            deck_id: None,
             parent_stream,
             stream: relm.stream().clone(),
         }
     }
 
+    fn init_view(&mut self) {
+        // Filters are kept as the search terms they correspond to
+        self.card_filter.append(Some(""), "All cards");
+        self.card_filter.append(Some("is:marked"), "Marked");
+        self.card_filter.append(Some("flag:any"), "Any flag");
+        for flag in Flag::ALL.iter() {
+            let term = format!("flag:{}", flag.name().to_lowercase());
+            self.card_filter
+                .append(Some(&term), &format!("{} flag", flag.name()));
+        }
+        self.card_filter.set_active_id(Some(""));
+    }
+
     fn update(&mut self, event: CardViewMsg) {
         match event {
-            CardViewMsg::Add(question, answer) => {
+            CardViewMsg::Add(question, answer, note) => {
+                // Still add the card, but point out where its twin lives so it
+                // can be cleaned up with the duplicate finder if unintended
+                match self.duplicate_warning(&question) {
+                    Some(warning) => self.alert.set_text(&warning),
+                    None => self.alert.set_text(""),
+                }
                 self.model
                     .parent_stream
-                    .emit(window::Msg::AddCard(question, answer));
+                    .emit(window::Msg::AddCard(question, answer, note));
             }
             CardViewMsg::Delete => {
-                if let Some(id) = self.model.active_card {
-                    self.model.parent_stream.emit(window::Msg::DeleteCard(id));
+                if let Some(uid) = self.model.active_card {
+                    self.model.parent_stream.emit(window::Msg::DeleteCard(uid));
                 }
             }
-            CardViewMsg::Edit(question, answer) => {
-                if let Some(id) = self.model.active_card {
+            CardViewMsg::Edit(question, answer, note) => {
+                if let Some(uid) = self.model.active_card {
                     self.model
                         .parent_stream
-                        .emit(window::Msg::EditCard(id, question, answer));
+                        .emit(window::Msg::EditCard(uid, question, answer, note));
                 }
             }
-            CardViewMsg::Selected(id) => {
-                self.model.active_card = Some(id);
+            CardViewMsg::Selected(uid) => {
+                self.model.active_card = Some(uid);
+                // Let the edit modal start out from the card's current content
+                let content = match self.model.collection.try_borrow() {
+                    Ok(c) => c
+                        .card_by_uid(uid)
+                        .map(|(_, card)| (card.question.clone(), card.answer.clone(), card.note.clone())),
+                    Err(_) => None,
+                };
+                let question = content.as_ref().map(|(question, _, _)| question.as_str());
                 self.active_card_label
-                    .set_text(&format!("Selected card: {}", id));
+                    .set_text(&format!("Selected card: {}", question.unwrap_or("")));
+                self.card_op_grid.emit(CardOpMsg::Selected(content));
             }
             CardViewMsg::Cleared => {
                 self.model.active_card = None;
                 self.active_card_label.set_text("Selected card: None");
+                self.card_op_grid.emit(CardOpMsg::Selected(None));
+            }
+            CardViewMsg::FilterChange => {
+                if let Some(id) = self.model.deck_id {
+                    self.model.stream.emit(CardViewMsg::UpdateToDeck(id));
+                }
             }
             CardViewMsg::UpdateToDeck(id) => {
+                self.model.deck_id = Some(id);
                 for (_id, widget) in self.model.card_map.drain() {
                     self.cards.remove_widget(widget);
                 }
+                let filter = match self.card_filter.get_active_id() {
+                    Some(term) => Query::parse(&term).unwrap_or_default(),
+                    None => Query::default(),
+                };
                 if let Ok(c) = self.model.collection.try_borrow() {
                     let deck_pos = c.deck_pos_by_id(id);
                     let deck = match deck_pos {
@@ -85,19 +128,40 @@ impl Widget for CardView {
                             return;
                         }
                     };
-                    for card in &deck.cards {
+                    for card in deck.cards.iter().filter(|card| filter.matches(deck, card)) {
+                        let mut marker = String::new();
+                        if let Some(flag) = card.flag {
+                            marker.push_str(&format!(" [{}]", flag.name()));
+                        }
+                        if card.marked {
+                            marker.push_str(" *");
+                        }
                         let widget = self.cards.add_widget::<CardWidget>((
                             card.question.clone(),
-                            card.id,
+                            marker,
+                            card.uid,
                             self.model.stream.clone(),
                         ));
-                        self.model.card_map.insert(card.id, widget);
+                        self.model.card_map.insert(card.uid, widget);
                     }
                 }
             }
         }
     }
 
+    // Describes where a card with the same question already lives, if any
+    fn duplicate_warning(&self, question: &str) -> Option<String> {
+        let c = self.model.collection.try_borrow().ok()?;
+        let found = c.find_question(question);
+        let (deck, _) = c.card_by_uid(*found.first()?)?;
+        let place = if Some(deck.id) == self.model.deck_id {
+            "this deck".to_string()
+        } else {
+            format!("deck \"{}\"", deck.title)
+        };
+        Some(format!("Heads up, a card in {} already has this question", place))
+    }
+
     view! {
         gtk::Box {
             orientation: gtk::Orientation::Vertical,
@@ -106,6 +170,12 @@ impl Widget for CardView {
             },
             #[name="active_card_label"]
             gtk::Label {},
+            #[name="alert"]
+            gtk::Label {},
+            #[name="card_filter"]
+            gtk::ComboBoxText {
+                changed => CardViewMsg::FilterChange,
+            },
             gtk::ScrolledWindow {
                 min_content_height: 80,
                 shadow_type: gtk::ShadowType::Out,
@@ -122,7 +192,7 @@ impl Widget for CardView {
 
 pub struct CardModel {
     card_name: String,
-    id: u32,
+    uid: Uuid,
     parent_stream: EventStream<CardViewMsg>,
 }
 
@@ -133,15 +203,17 @@ pub enum CardMsg {
 
 #[widget]
 impl Widget for CardWidget {
-    fn model((card_name, id, parent_stream): (String, u32, EventStream<CardViewMsg>)) -> CardModel {
+    fn model(
+        (card_name, marker, uid, parent_stream): (String, String, Uuid, EventStream<CardViewMsg>),
+    ) -> CardModel {
         let mut name = card_name;
         if name.chars().count() > 20 {
             name = name.chars().take(20).collect::<String>();
             name.push_str("...");
         }
         CardModel {
-            card_name: format!("{}: {}", id, name),
-            id,
+            card_name: format!("{}{}", name, marker),
+            uid,
             parent_stream,
         }
     }
@@ -151,7 +223,7 @@ impl Widget for CardWidget {
             CardMsg::Selected => {
                 self.model
                     .parent_stream
-                    .emit(CardViewMsg::Selected(self.model.id));
+                    .emit(CardViewMsg::Selected(self.model.uid));
             }
         }
     }
@@ -4,6 +4,7 @@ use relm::{Component, ContainerWidget, EventStream, Relm, Widget};
 use relm_derive::{widget, Msg};
 
 use super::deck_view::DeckViewMsg;
+use crate::collection::Query;
 
 // I wish that ModalState(below) could be written like the below snippet, so that
 // I could more easily reduce the combinatorial checking below. It seems that
@@ -21,6 +22,7 @@ enum ModalState {
     Add(Component<AddDeckWidget>),
     Delete(Component<DeleteDeckWidget>),
     Edit(Component<EditDeckWidget>),
+    Filter(Component<FilterDeckWidget>),
 }
 
 // ----- Operations Grid Section -----
@@ -41,6 +43,10 @@ pub enum DeckOpMsg {
     DeleteModal,
     Edit(String),
     EditModal,
+    Empty,
+    Filter(String, String, bool),
+    FilterModal,
+    Rebuild,
 }
 
 #[widget]
@@ -88,6 +94,23 @@ impl Widget for DeckOpGrid {
                     .add_widget::<EditDeckWidget>(self.model.stream.clone());
                 self.model.modal_state = Some(ModalState::Edit(widget));
             }
+            DeckOpMsg::Empty => {
+                self.model.parent_stream.emit(DeckViewMsg::Empty);
+            }
+            DeckOpMsg::Filter(title, query, reschedule) => {
+                self.model
+                    .parent_stream
+                    .emit(DeckViewMsg::AddFiltered(title, query, reschedule));
+            }
+            DeckOpMsg::FilterModal => {
+                let widget = self
+                    .mod_box
+                    .add_widget::<FilterDeckWidget>(self.model.stream.clone());
+                self.model.modal_state = Some(ModalState::Filter(widget));
+            }
+            DeckOpMsg::Rebuild => {
+                self.model.parent_stream.emit(DeckViewMsg::Rebuild);
+            }
         }
     }
 
@@ -100,6 +123,7 @@ impl Widget for DeckOpGrid {
             Some(Add(widget)) => self.mod_box.remove_widget(widget.clone()),
             Some(Delete(widget)) => self.mod_box.remove_widget(widget.clone()),
             Some(Edit(widget)) => self.mod_box.remove_widget(widget.clone()),
+            Some(Filter(widget)) => self.mod_box.remove_widget(widget.clone()),
         }
     }
 
@@ -144,6 +168,30 @@ impl Widget for DeckOpGrid {
                         top_attach: 1,
                     },
                     clicked => DeckOpMsg::Cancel,
+                },
+                gtk::Button {
+                    label: "Filtered",
+                    cell: {
+                        left_attach: 0,
+                        top_attach: 2,
+                    },
+                    clicked => DeckOpMsg::FilterModal,
+                },
+                gtk::Button {
+                    label: "Rebuild",
+                    cell: {
+                        left_attach: 1,
+                        top_attach: 2,
+                    },
+                    clicked => DeckOpMsg::Rebuild,
+                },
+                gtk::Button {
+                    label: "Empty",
+                    cell: {
+                        left_attach: 0,
+                        top_attach: 3,
+                    },
+                    clicked => DeckOpMsg::Empty,
                 }
             },
             #[name="mod_box"]
@@ -320,3 +368,77 @@ impl Widget for EditDeckWidget {
         }
     }
 }
+
+// ----- Filtered Deck Modal Widget -----
+// Builds a filtered deck out of the cards matching a search query
+pub struct FilterDeckModel {
+    parent_stream: EventStream<DeckOpMsg>,
+}
+
+#[derive(Msg)]
+pub enum FilterDeckMsg {
+    Build,
+}
+
+#[widget]
+impl Widget for FilterDeckWidget {
+    fn model(parent_stream: EventStream<DeckOpMsg>) -> FilterDeckModel {
+        FilterDeckModel { parent_stream }
+    }
+
+    fn update(&mut self, event: FilterDeckMsg) {
+        match event {
+            FilterDeckMsg::Build => {
+                let (title, query) = match (self.title.get_text(), self.query.get_text()) {
+                    (Some(title), Some(query)) => (title.to_string(), query.to_string()),
+                    _ => {
+                        println!("error: failed to get text from filtered deck fields");
+                        return;
+                    }
+                };
+                // Don't add a nameless deck
+                if title.is_empty() {
+                    self.alert.set_text("The filtered deck needs a name");
+                    return;
+                }
+                if let Err(e) = Query::parse(&query) {
+                    self.alert.set_text(&e);
+                    return;
+                }
+                self.model.parent_stream.emit(DeckOpMsg::Filter(
+                    title,
+                    query,
+                    self.reschedule.get_active(),
+                ));
+            }
+        }
+    }
+
+    view! {
+        gtk::Box {
+            orientation: gtk::Orientation::Vertical,
+            gtk::Label {
+                label: "Filtered Deck",
+            },
+            #[name="alert"]
+            gtk::Label {},
+            #[name="title"]
+            gtk::Entry {
+                placeholder_text: Some("Name"),
+            },
+            #[name="query"]
+            gtk::Entry {
+                placeholder_text: Some("Search, e.g. deck:Chemistry failed:3"),
+            },
+            #[name="reschedule"]
+            gtk::CheckButton {
+                label: "Reschedule cards based on my answers",
+                active: true,
+            },
+            gtk::Button {
+                label: "Build",
+                clicked => FilterDeckMsg::Build,
+            }
+        }
+    }
+}
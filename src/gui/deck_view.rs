@@ -24,8 +24,11 @@ pub struct DeckViewModel {
 #[derive(Msg)]
 pub enum DeckViewMsg {
     Add(String),
+    AddFiltered(String, String, bool),
     Delete,
     Edit(String),
+    Empty,
+    Rebuild,
     NewCollection,
     Selected(u32),
     Cleared,
@@ -51,6 +54,21 @@ impl Widget for DeckView {
             DeckViewMsg::Add(name) => {
                 self.model.parent_stream.emit(Msg::AddDeck(name));
             }
+            DeckViewMsg::AddFiltered(title, query, reschedule) => {
+                self.model
+                    .parent_stream
+                    .emit(Msg::AddFilteredDeck(title, query, reschedule));
+            }
+            DeckViewMsg::Empty => {
+                if let Some(id) = self.model.active_deck {
+                    self.model.parent_stream.emit(Msg::EmptyDeck(id));
+                }
+            }
+            DeckViewMsg::Rebuild => {
+                if let Some(id) = self.model.active_deck {
+                    self.model.parent_stream.emit(Msg::RebuildDeck(id));
+                }
+            }
             DeckViewMsg::Delete => {
                 if let Some(id) = self.model.active_deck {
                     self.model.parent_stream.emit(Msg::DeleteDeck(id));
@@ -82,9 +100,14 @@ impl Widget for DeckView {
         }
         if let Ok(c) = self.model.collection.try_borrow() {
             for deck in &c.decks {
+                let title = match deck.filter {
+                    Some(_) => format!("{} (filtered)", deck.title),
+                    None => deck.title.clone(),
+                };
                 let widget = self.decks.add_widget::<DeckWidget>((
                     deck.id,
-                    deck.title.clone(),
+                    title,
+                    c.due_count(deck.id),
                     self.model.stream.clone(),
                 ));
                 self.model.deck_map.insert(deck.id, widget);
@@ -129,7 +152,9 @@ pub enum DeckMsg {
 
 #[widget]
 impl Widget for DeckWidget {
-    fn model((id, deck_name, parent_stream): (u32, String, EventStream<DeckViewMsg>)) -> DeckModel {
+    fn model(
+        (id, deck_name, due, parent_stream): (u32, String, usize, EventStream<DeckViewMsg>),
+    ) -> DeckModel {
         let mut name = deck_name;
         if name.chars().count() > 20 {
             name = name.chars().take(20).collect::<String>();
@@ -137,7 +162,7 @@ impl Widget for DeckWidget {
         }
         DeckModel {
             id,
-            deck_name: format!("{}: {}", id, name),
+            deck_name: format!("{}: {} ({} due)", id, name, due),
             parent_stream,
         }
     }
@@ -1,8 +1,13 @@
+mod backup_view;
+mod card_browser;
 mod card_operations;
 mod card_view;
 mod deck_operations;
 mod deck_view;
+mod profile_view;
 mod quiz;
+mod trash_view;
 mod window;
 
+pub use self::profile_view::choose_profile;
 pub use self::window::*;
@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::*;
+
+use relm::{EventStream, Widget};
+use relm_derive::{widget, Msg};
+
+use super::window;
+use crate::collection::{Collection, Profile};
+
+// Columns of the backing list store. The first two are displayed, the last
+// identifies the selected profile.
+const NAME_COL: u32 = 0;
+const OPEN_COL: u32 = 1;
+const ID_COL: u32 = 2;
+
+// (title, displayed column, column to sort by)
+const DISPLAYED_COLUMNS: [(&str, u32, u32); 2] =
+    [("Profile", NAME_COL, NAME_COL), ("", OPEN_COL, OPEN_COL)];
+
+/// Asks which profile to open before the main window is up, returning None if
+/// the question is dismissed
+pub fn choose_profile(profiles: &[Profile]) -> Option<u32> {
+    if gtk::init().is_err() {
+        println!("error: failed to initialise GTK");
+        return None;
+    }
+    let dialog = gtk::Dialog::new();
+    dialog.set_title("Choose a Profile");
+    dialog.set_border_width(10);
+    let choices = gtk::ComboBoxText::new();
+    for profile in profiles {
+        choices.append_text(&profile.name);
+    }
+    choices.set_active(Some(0));
+    dialog.get_content_area().add(&choices);
+    dialog.add_button("Open", gtk::ResponseType::Ok);
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+    dialog.show_all();
+
+    let response = dialog.run();
+    let chosen = choices.get_active();
+    dialog.close();
+    if response != gtk::ResponseType::Ok {
+        return None;
+    }
+    chosen
+        .and_then(|pos| profiles.get(pos as usize))
+        .map(|profile| profile.id)
+}
+
+// ----- Profiles Window -----
+// A separate window for making, renaming, deleting and switching between the
+// named collections kept in the data directory
+pub struct ProfileViewModel {
+    collection: Rc<RefCell<Collection>>,
+    parent_stream: EventStream<window::Msg>,
+    profiles: Vec<Profile>,
+    store: gtk::ListStore,
+}
+
+#[derive(Msg)]
+pub enum ProfileMsg {
+    Close,
+    Create,
+    Delete,
+    Listed(Vec<Profile>),
+    Open,
+    Refresh,
+    Rename,
+}
+
+#[widget]
+impl Widget for ProfileView {
+    fn model(
+        (collection, parent_stream): (Rc<RefCell<Collection>>, EventStream<window::Msg>),
+    ) -> ProfileViewModel {
+        let store = gtk::ListStore::new(&[gtk::Type::String, gtk::Type::String, gtk::Type::U32]);
+        ProfileViewModel {
+            collection,
+            parent_stream,
+            profiles: vec![],
+            store,
+        }
+    }
+
+    fn init_view(&mut self) {
+        self.tree.set_model(Some(&self.model.store));
+        for (title, display_col, sort_col) in DISPLAYED_COLUMNS.iter() {
+            let cell = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", *display_col as i32);
+            column.set_sort_column_id(*sort_col as i32);
+            self.tree.append_column(&column);
+        }
+    }
+
+    fn update(&mut self, event: ProfileMsg) {
+        match event {
+            ProfileMsg::Close => {
+                self.model.parent_stream.emit(window::Msg::CloseProfiles);
+            }
+            ProfileMsg::Create => match self.name() {
+                Some(name) => {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::CreateProfile(name));
+                    self.name_entry.set_text("");
+                }
+                None => self.alert.set_text("Enter a name for the new profile"),
+            },
+            ProfileMsg::Delete => match self.selected() {
+                Some(id) if Some(id) == self.current() => {
+                    self.alert
+                        .set_text("Open another profile before deleting this one");
+                }
+                Some(id) => {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::DeleteProfile(id));
+                }
+                None => self.alert.set_text("Select a profile to delete first"),
+            },
+            ProfileMsg::Listed(profiles) => {
+                self.model.profiles = profiles;
+                self.populate();
+            }
+            ProfileMsg::Open => match self.selected() {
+                Some(id) => {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::SwitchProfile(id));
+                }
+                None => self.alert.set_text("Select a profile to open first"),
+            },
+            ProfileMsg::Refresh => {
+                self.populate();
+            }
+            ProfileMsg::Rename => match (self.selected(), self.name()) {
+                (Some(id), Some(name)) => {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::RenameProfile(id, name));
+                    self.name_entry.set_text("");
+                }
+                _ => self
+                    .alert
+                    .set_text("Select a profile and enter its new name first"),
+            },
+        }
+    }
+
+    // Rebuild the rows of the table, marking the profile that is open
+    fn populate(&mut self) {
+        self.model.store.clear();
+        self.alert.set_text("");
+        let current = self.current();
+        for profile in self.model.profiles.iter() {
+            let open = if Some(profile.id) == current {
+                "Open"
+            } else {
+                ""
+            };
+            self.model.store.insert_with_values(
+                None,
+                &[NAME_COL, OPEN_COL, ID_COL],
+                &[&profile.name, &open, &profile.id],
+            );
+        }
+    }
+
+    fn current(&self) -> Option<u32> {
+        self.model.collection.try_borrow().ok().map(|c| c.id)
+    }
+
+    fn name(&self) -> Option<String> {
+        let name = self.name_entry.get_text()?.trim().to_string();
+        if name.is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
+    fn selected(&self) -> Option<u32> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        model.get_value(&iter, ID_COL as i32).get_some::<u32>().ok()
+    }
+
+    view! {
+        gtk::Window {
+            title: "Profiles",
+            border_width: 10,
+            default_width: 400,
+            default_height: 300,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                #[name="alert"]
+                gtk::Label {},
+                gtk::ScrolledWindow {
+                    child: {
+                        expand: true,
+                    },
+                    shadow_type: gtk::ShadowType::Out,
+                    #[name="tree"]
+                    gtk::TreeView {},
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Button {
+                        label: "Open",
+                        clicked => ProfileMsg::Open,
+                    },
+                    gtk::Button {
+                        label: "Delete",
+                        clicked => ProfileMsg::Delete,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="name_entry"]
+                    gtk::Entry {
+                        placeholder_text: Some("Profile name"),
+                    },
+                    gtk::Button {
+                        label: "New",
+                        clicked => ProfileMsg::Create,
+                    },
+                    gtk::Button {
+                        label: "Rename",
+                        clicked => ProfileMsg::Rename,
+                    },
+                },
+            },
+            delete_event(_, _) => (ProfileMsg::Close, Inhibit(false)),
+        }
+    }
+}
@@ -1,12 +1,23 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use gtk::*;
 
 use relm::{Component, ContainerWidget, EventStream, Relm, Widget};
 use relm_derive::{widget, Msg};
+use uuid::Uuid;
 
 use super::window;
+use crate::collection::{Collection, CramOrder, Flag, QuizCard};
 
 pub struct QuizModel {
     active_quiz: Option<Component<StartedQuiz>>,
+    collection: Rc<RefCell<Collection>>,
+    // Whether the active quiz is a cram session, whose results are discarded
+    // rather than fed back into scheduling
+    cramming: bool,
+    // Decks to quiz or cram on, with nothing ticked meaning all of them
+    deck_checks: Vec<(u32, gtk::CheckButton)>,
     parent_stream: EventStream<window::Msg>,
     stream: EventStream<QuizMsg>,
 }
@@ -14,47 +25,119 @@ pub struct QuizModel {
 #[derive(Msg)]
 pub enum QuizMsg {
     StartQuiz,
-    GotQuiz(Vec<(u32, u32, String, String)>),
-    QuizComplete(Vec<(u32, u32, Option<bool>)>),
+    StartDeckQuiz,
+    StartCram,
+    GotQuiz(Vec<QuizCard>),
+    GotCram(Vec<QuizCard>),
+    NewCollection,
+    Key(u32),
+    SetFlag(Uuid, Option<Flag>),
+    SetMarked(Uuid, bool),
+    QuizComplete(Vec<(Uuid, Option<bool>)>),
 }
 
 #[widget]
 impl Widget for QuizView {
-    fn model(relm: &Relm<Self>, parent_stream: EventStream<window::Msg>) -> QuizModel {
+    fn model(
+        relm: &Relm<Self>,
+        (collection, parent_stream): (Rc<RefCell<Collection>>, EventStream<window::Msg>),
+    ) -> QuizModel {
         QuizModel {
             active_quiz: None,
+            collection,
+            cramming: false,
+            deck_checks: Vec::new(),
             parent_stream,
             stream: relm.stream().clone(),
         }
     }
 
+    fn init_view(&mut self) {
+        self.cram_order.append(Some("added"), "In order added");
+        self.cram_order.append(Some("random"), "Random order");
+        self.cram_order.append(Some("hardest"), "Hardest first");
+        self.cram_order.set_active_id(Some("added"));
+        self.update_decks();
+    }
+
     fn update(&mut self, event: QuizMsg) {
         use QuizMsg::*;
 
+        // Neither deck changes nor flagging should clear out the alert
+        match event {
+            NewCollection => {
+                self.update_decks();
+                return;
+            }
+            Key(keyval) => {
+                if let Some(quiz) = &self.model.active_quiz {
+                    quiz.emit(StartedQuizMsg::Key(keyval));
+                }
+                return;
+            }
+            SetFlag(uid, flag) => {
+                self.model.parent_stream.emit(window::Msg::SetFlag(uid, flag));
+                return;
+            }
+            SetMarked(uid, marked) => {
+                self.model
+                    .parent_stream
+                    .emit(window::Msg::SetMarked(uid, marked));
+                return;
+            }
+            _ => {}
+        }
+
         self.alert.set_text("");
         match event {
             StartQuiz => {
-                self.model.parent_stream.emit(window::Msg::StartQuiz);
+                let deck_ids = self.ticked_decks();
+                let scope = if deck_ids.is_empty() {
+                    None
+                } else {
+                    Some(deck_ids)
+                };
+                self.model.parent_stream.emit(window::Msg::StartQuiz(scope));
             }
-            GotQuiz(quiz) => {
-                // Don't start a new quiz if we already have one active
-                if let Some(_) = self.model.active_quiz {
-                    return;
-                }
-                // Also don't start a quiz if it has no entries
-                if quiz.len() < 1 {
-                    self.alert.set_text("No quiz entries are up for review!");
+            StartDeckQuiz => {
+                self.model.parent_stream.emit(window::Msg::StartDeckQuiz);
+            }
+            StartCram => {
+                let deck_ids = self.ticked_decks();
+                if deck_ids.is_empty() {
+                    self.alert.set_text("Tick at least one deck to cram!");
                     return;
                 }
-                let widget = self
-                    .quiz_box
-                    .add_widget::<StartedQuiz>((self.model.stream.clone(), quiz));
-                self.model.active_quiz = Some(widget);
-            }
-            QuizComplete(results) => {
+                let order = match self.cram_order.get_active_id().as_ref().map(|id| id.as_str()) {
+                    Some("random") => CramOrder::Random,
+                    Some("hardest") => CramOrder::Hardest,
+                    _ => CramOrder::Added,
+                };
                 self.model
                     .parent_stream
-                    .emit(window::Msg::QuizComplete(results));
+                    .emit(window::Msg::StartCram(deck_ids, order));
+            }
+            GotQuiz(quiz) => {
+                self.start(quiz, false);
+            }
+            GotCram(quiz) => {
+                self.start(quiz, true);
+            }
+            NewCollection | Key(_) | SetFlag(..) | SetMarked(..) => {}
+            QuizComplete(results) => {
+                if self.model.cramming {
+                    let answered = results.iter().filter(|r| r.1.is_some()).count();
+                    let recalled = results.iter().filter(|r| r.1 == Some(true)).count();
+                    self.alert.set_text(&format!(
+                        "Cram finished, recalled {} of {} cards",
+                        recalled, answered
+                    ));
+                } else {
+                    self.model
+                        .parent_stream
+                        .emit(window::Msg::QuizComplete(results));
+                }
+                self.model.cramming = false;
                 if let Some(widget) = self.model.active_quiz.take() {
                     self.quiz_box.remove_widget(widget);
                 }
@@ -62,16 +145,93 @@ impl Widget for QuizView {
         }
     }
 
+    fn start(&mut self, quiz: Vec<QuizCard>, cramming: bool) {
+        // Don't start a new quiz if we already have one active
+        if let Some(_) = self.model.active_quiz {
+            return;
+        }
+        // Also don't start a quiz if it has no entries
+        if quiz.len() < 1 {
+            if cramming {
+                self.alert.set_text("The selected decks have no cards!");
+            } else {
+                self.alert.set_text("No quiz entries are up for review!");
+            }
+            return;
+        }
+        let widget = self.quiz_box.add_widget::<StartedQuiz>((
+            self.model.stream.clone(),
+            quiz,
+            self.show_notes.get_active(),
+        ));
+        self.model.active_quiz = Some(widget);
+        self.model.cramming = cramming;
+    }
+
+    fn ticked_decks(&self) -> Vec<u32> {
+        self.model
+            .deck_checks
+            .iter()
+            .filter(|(_, check)| check.get_active())
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    // Rebuild the deck check boxes, keeping previous selections
+    fn update_decks(&mut self) {
+        let mut checked = vec![];
+        for (id, check) in self.model.deck_checks.drain(..) {
+            if check.get_active() {
+                checked.push(id);
+            }
+            self.decks.remove(&check);
+        }
+        if let Ok(c) = self.model.collection.try_borrow() {
+            for deck in &c.decks {
+                let check = gtk::CheckButton::new();
+                check.set_label(&deck.title);
+                check.set_active(checked.contains(&deck.id));
+                self.decks.add(&check);
+                check.show();
+                self.model.deck_checks.push((deck.id, check));
+            }
+        }
+    }
+
     view! {
         gtk::Box {
             orientation: gtk::Orientation::Vertical,
             halign: gtk::Align::Center,
             #[name="alert"]
             gtk::Label {},
+            gtk::Label {
+                label: "Decks to study (none ticked for all)",
+            },
+            #[name="decks"]
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+            },
             gtk::Button {
                 label: "Quiz Me!",
                 clicked => QuizMsg::StartQuiz,
             },
+            gtk::Button {
+                label: "Quiz Selected Deck",
+                clicked => QuizMsg::StartDeckQuiz,
+            },
+            #[name="show_notes"]
+            gtk::CheckButton {
+                label: "Show notes with answers",
+            },
+            gtk::Label {
+                label: "Cram (leaves scheduling alone)",
+            },
+            #[name="cram_order"]
+            gtk::ComboBoxText {},
+            gtk::Button {
+                label: "Cram Ticked Decks",
+                clicked => QuizMsg::StartCram,
+            },
             #[name="quiz_box"]
             gtk::Box {}
         }
@@ -80,15 +240,19 @@ impl Widget for QuizView {
 
 pub struct StartedQuizModel {
     active_answer: String,
+    active_note: String,
+    card_status: String,
     parent_stream: EventStream<QuizMsg>,
     question_idx: usize,
-    quiz_vec: Vec<(u32, u32, String, String, Option<bool>)>,
+    quiz_vec: Vec<(QuizCard, Option<bool>)>,
+    show_notes: bool,
 }
 
 #[derive(Msg)]
 pub enum StartedQuizMsg {
     Correct,
     Incorrect,
+    Key(u32),
     Reveal,
     Stop,
 }
@@ -96,27 +260,39 @@ pub enum StartedQuizMsg {
 #[widget]
 impl Widget for StartedQuiz {
     fn model(
-        (parent_stream, quiz): (EventStream<QuizMsg>, Vec<(u32, u32, String, String)>),
+        (parent_stream, quiz, show_notes): (EventStream<QuizMsg>, Vec<QuizCard>, bool),
     ) -> StartedQuizModel {
-        let quiz_vec: Vec<(u32, u32, String, String, Option<bool>)> = quiz
-            .into_iter()
-            .map(|question| (question.0, question.1, question.2, question.3, None))
-            .collect();
+        let quiz_vec: Vec<(QuizCard, Option<bool>)> =
+            quiz.into_iter().map(|question| (question, None)).collect();
+        let card_status = match quiz_vec.first() {
+            Some((card, _)) => status(card),
+            None => "".to_string(),
+        };
         StartedQuizModel {
             active_answer: "...".to_string(),
+            active_note: "".to_string(),
+            card_status,
             parent_stream,
             question_idx: 0,
             quiz_vec,
+            show_notes,
         }
     }
 
     fn update(&mut self, event: StartedQuizMsg) {
         use StartedQuizMsg::*;
 
+        // Flagging and marking leave the revealed answer up
+        if let Key(keyval) = event {
+            self.handle_key(keyval);
+            return;
+        }
+
         self.model.active_answer = "...".to_string();
+        self.model.active_note = "".to_string();
         match event {
             Correct => {
-                self.model.quiz_vec[self.model.question_idx].4 = Some(true);
+                self.model.quiz_vec[self.model.question_idx].1 = Some(true);
                 if self.model.question_idx == (self.model.quiz_vec.len() - 1) {
                     self.report_results();
                 } else {
@@ -124,7 +300,7 @@ impl Widget for StartedQuiz {
                 }
             }
             Incorrect => {
-                self.model.quiz_vec[self.model.question_idx].4 = Some(false);
+                self.model.quiz_vec[self.model.question_idx].1 = Some(false);
                 if self.model.question_idx == (self.model.quiz_vec.len() - 1) {
                     self.report_results();
                 } else {
@@ -132,12 +308,46 @@ impl Widget for StartedQuiz {
                 }
             }
             Reveal => {
-                self.model.active_answer = self.model.quiz_vec[self.model.question_idx].3.clone();
+                let card = &self.model.quiz_vec[self.model.question_idx].0;
+                self.model.active_answer = card.answer.clone();
+                if self.model.show_notes {
+                    self.model.active_note = card.note.clone();
+                }
             }
             Stop => {
                 self.report_results();
             }
+            Key(_) => {}
+        }
+        let card = &self.model.quiz_vec[self.model.question_idx].0;
+        self.model.card_status = status(card);
+    }
+
+    // Ctrl+1 to Ctrl+4 toggle the flag colours, Ctrl+0 clears the flag and
+    // Ctrl+M toggles whether the card is marked
+    fn handle_key(&mut self, keyval: u32) {
+        let card = &mut self.model.quiz_vec[self.model.question_idx].0;
+        let key = std::char::from_u32(keyval).map(|c| c.to_ascii_lowercase());
+        match key {
+            Some('m') => {
+                card.marked = !card.marked;
+                self.model
+                    .parent_stream
+                    .emit(QuizMsg::SetMarked(card.uid, card.marked));
+            }
+            Some(c @ '0'..='4') => {
+                let flag = match c.to_digit(10) {
+                    Some(n) if n > 0 => Some(Flag::ALL[n as usize - 1]),
+                    _ => None,
+                };
+                card.flag = if card.flag == flag { None } else { flag };
+                self.model
+                    .parent_stream
+                    .emit(QuizMsg::SetFlag(card.uid, card.flag));
+            }
+            _ => return,
         }
+        self.model.card_status = status(card);
     }
 
     fn report_results(&self) {
@@ -145,7 +355,7 @@ impl Widget for StartedQuiz {
             .model
             .quiz_vec
             .iter()
-            .map(|question| (question.0, question.1, question.4))
+            .map(|(card, result)| (card.uid, *result))
             .collect();
         self.model
             .parent_stream
@@ -159,11 +369,17 @@ impl Widget for StartedQuiz {
                 label: "Active Quiz",
             },
             gtk::Label {
-                label: &self.model.quiz_vec[self.model.question_idx].2,
+                label: &self.model.quiz_vec[self.model.question_idx].0.question,
             },
             gtk::Label {
                 label: &self.model.active_answer,
             },
+            gtk::Label {
+                label: &self.model.active_note,
+            },
+            gtk::Label {
+                label: &self.model.card_status,
+            },
             gtk::Box {
                 orientation: gtk::Orientation::Horizontal,
                 gtk::Button {
@@ -186,3 +402,16 @@ impl Widget for StartedQuiz {
         }
     }
 }
+
+// Describes the flag and mark on a card, along with how to change them
+fn status(card: &QuizCard) -> String {
+    let flag = match card.flag {
+        Some(flag) => flag.name(),
+        None => "None",
+    };
+    let marked = if card.marked { "yes" } else { "no" };
+    format!(
+        "Flag: {} (Ctrl+1-4, Ctrl+0 to clear), Marked: {} (Ctrl+M)",
+        flag, marked
+    )
+}
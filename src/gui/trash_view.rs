@@ -0,0 +1,279 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk::*;
+
+use relm::{EventStream, Widget};
+use relm_derive::{widget, Msg};
+use uuid::Uuid;
+
+use super::card_browser::{format_date, secs_since_epoch};
+use super::window;
+use crate::collection::Collection;
+
+// Columns of the backing list store. The first four are displayed, the rest
+// hold raw values used for sorting and for identifying the selected item.
+const KIND_COL: u32 = 0;
+const DECK_COL: u32 = 1;
+const CONTENT_COL: u32 = 2;
+const DELETED_COL: u32 = 3;
+const DELETED_SECS_COL: u32 = 4;
+const IS_DECK_COL: u32 = 5;
+const DECK_ID_COL: u32 = 6;
+const CARD_UID_COL: u32 = 7;
+
+// (title, displayed column, column to sort by)
+const DISPLAYED_COLUMNS: [(&str, u32, u32); 4] = [
+    ("Kind", KIND_COL, KIND_COL),
+    ("Deck", DECK_COL, DECK_COL),
+    ("Content", CONTENT_COL, CONTENT_COL),
+    ("Deleted", DELETED_COL, DELETED_SECS_COL),
+];
+
+enum Trashed {
+    Deck(u32),
+    Card(Uuid),
+}
+
+// ----- Trash Window -----
+// A separate window listing deleted decks and cards, which can be restored or
+// purged for good
+pub struct TrashViewModel {
+    collection: Rc<RefCell<Collection>>,
+    parent_stream: EventStream<window::Msg>,
+    store: gtk::ListStore,
+}
+
+#[derive(Msg)]
+pub enum TrashMsg {
+    Close,
+    Empty,
+    Purge,
+    Refresh,
+    Restore,
+    SetRetention,
+}
+
+#[widget]
+impl Widget for TrashView {
+    fn model(
+        (collection, parent_stream): (Rc<RefCell<Collection>>, EventStream<window::Msg>),
+    ) -> TrashViewModel {
+        let store = gtk::ListStore::new(&[
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::String,
+            gtk::Type::U64,
+            gtk::Type::Bool,
+            gtk::Type::U32,
+            gtk::Type::String,
+        ]);
+        TrashViewModel {
+            collection,
+            parent_stream,
+            store,
+        }
+    }
+
+    fn init_view(&mut self) {
+        self.tree.set_model(Some(&self.model.store));
+        for (title, display_col, sort_col) in DISPLAYED_COLUMNS.iter() {
+            let cell = gtk::CellRendererText::new();
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.pack_start(&cell, true);
+            column.add_attribute(&cell, "text", *display_col as i32);
+            column.set_sort_column_id(*sort_col as i32);
+            self.tree.append_column(&column);
+        }
+        self.retention.set_range(1.0, 3650.0);
+        self.retention.set_increments(1.0, 30.0);
+        self.populate();
+    }
+
+    fn update(&mut self, event: TrashMsg) {
+        match event {
+            TrashMsg::Close => {
+                self.model.parent_stream.emit(window::Msg::CloseTrash);
+            }
+            TrashMsg::Empty => {
+                self.model.parent_stream.emit(window::Msg::EmptyTrash);
+            }
+            TrashMsg::Purge => match self.selected() {
+                Some(Trashed::Deck(id)) => {
+                    self.model.parent_stream.emit(window::Msg::PurgeDeck(id));
+                }
+                Some(Trashed::Card(uid)) => {
+                    self.model.parent_stream.emit(window::Msg::PurgeCard(uid));
+                }
+                None => self.alert.set_text("Select something to purge first"),
+            },
+            TrashMsg::Refresh => {
+                self.populate();
+            }
+            TrashMsg::Restore => match self.selected() {
+                Some(Trashed::Deck(id)) => {
+                    self.model.parent_stream.emit(window::Msg::RestoreDeck(id));
+                }
+                Some(Trashed::Card(uid)) => {
+                    // Cards can only go back into a deck that still exists
+                    let deck_exists = match self.model.collection.try_borrow() {
+                        Ok(c) => c
+                            .trash
+                            .cards
+                            .iter()
+                            .find(|trashed| trashed.card.uid == uid)
+                            .map_or(false, |trashed| c.deck_pos_by_id(trashed.deck_id).is_some()),
+                        Err(_) => return,
+                    };
+                    if deck_exists {
+                        self.model.parent_stream.emit(window::Msg::RestoreCard(uid));
+                    } else {
+                        self.alert
+                            .set_text("The card's deck was deleted too, restore the deck first");
+                    }
+                }
+                None => self.alert.set_text("Select something to restore first"),
+            },
+            TrashMsg::SetRetention => {
+                let days = self.retention.get_value_as_int().max(1) as u32;
+                self.model
+                    .parent_stream
+                    .emit(window::Msg::SetTrashRetention(days));
+            }
+        }
+    }
+
+    // Rebuild the rows of the table from the trash of the current collection
+    fn populate(&mut self) {
+        self.model.store.clear();
+        self.alert.set_text("");
+        let c = match self.model.collection.try_borrow() {
+            Ok(c) => c,
+            Err(_) => return,
+        };
+        self.retention
+            .set_value(c.trash.retention_days as f64);
+        self.count_label.set_text(&format!(
+            "{} decks and {} cards in the trash",
+            c.trash.decks.len(),
+            c.trash.cards.len()
+        ));
+        let columns = [
+            KIND_COL,
+            DECK_COL,
+            CONTENT_COL,
+            DELETED_COL,
+            DELETED_SECS_COL,
+            IS_DECK_COL,
+            DECK_ID_COL,
+            CARD_UID_COL,
+        ];
+        for trashed in c.trash.decks.iter() {
+            self.model.store.insert_with_values(
+                None,
+                &columns,
+                &[
+                    &"Deck",
+                    &trashed.deck.title,
+                    &format!("{} cards", trashed.deck.cards.len()),
+                    &format_date(trashed.deleted),
+                    &secs_since_epoch(trashed.deleted),
+                    &true,
+                    &trashed.deck.id,
+                    &trashed.deck.uid.to_string(),
+                ],
+            );
+        }
+        for trashed in c.trash.cards.iter() {
+            self.model.store.insert_with_values(
+                None,
+                &columns,
+                &[
+                    &"Card",
+                    &trashed.deck_title,
+                    &trashed.card.question,
+                    &format_date(trashed.deleted),
+                    &secs_since_epoch(trashed.deleted),
+                    &false,
+                    &trashed.deck_id,
+                    &trashed.card.uid.to_string(),
+                ],
+            );
+        }
+    }
+
+    fn selected(&self) -> Option<Trashed> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        let is_deck = model
+            .get_value(&iter, IS_DECK_COL as i32)
+            .get_some::<bool>()
+            .ok()?;
+        let deck_id = model
+            .get_value(&iter, DECK_ID_COL as i32)
+            .get_some::<u32>()
+            .ok()?;
+        if is_deck {
+            return Some(Trashed::Deck(deck_id));
+        }
+        let uid = model
+            .get_value(&iter, CARD_UID_COL as i32)
+            .get::<String>()
+            .ok()??;
+        Some(Trashed::Card(uid.parse().ok()?))
+    }
+
+    view! {
+        gtk::Window {
+            title: "Trash",
+            border_width: 10,
+            default_width: 700,
+            default_height: 400,
+            gtk::Box {
+                orientation: gtk::Orientation::Vertical,
+                #[name="alert"]
+                gtk::Label {},
+                #[name="count_label"]
+                gtk::Label {},
+                gtk::ScrolledWindow {
+                    child: {
+                        expand: true,
+                    },
+                    shadow_type: gtk::ShadowType::Out,
+                    #[name="tree"]
+                    gtk::TreeView {},
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Button {
+                        label: "Restore",
+                        clicked => TrashMsg::Restore,
+                    },
+                    gtk::Button {
+                        label: "Purge",
+                        clicked => TrashMsg::Purge,
+                    },
+                    gtk::Button {
+                        label: "Empty Trash",
+                        clicked => TrashMsg::Empty,
+                    },
+                },
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Label {
+                        label: "Days to keep deleted items",
+                    },
+                    #[name="retention"]
+                    gtk::SpinButton {},
+                    gtk::Button {
+                        label: "Set",
+                        clicked => TrashMsg::SetRetention,
+                    },
+                },
+            },
+            delete_event(_, _) => (TrashMsg::Close, Inhibit(false)),
+        }
+    }
+}
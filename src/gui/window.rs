@@ -5,10 +5,15 @@ use std::sync::mpsc;
 use gtk::*;
 use relm::{interval, Component, ContainerWidget, EventStream, Relm, Widget};
 use relm_derive::{widget, Msg};
+use uuid::Uuid;
 
+use super::backup_view::{BackupMsg, BackupView};
+use super::card_browser::{CardBrowser, CardBrowserMsg};
 use super::card_view::{CardView, CardViewMsg};
 use super::deck_view::{DeckView, DeckViewMsg};
+use super::profile_view::{ProfileMsg, ProfileView};
 use super::quiz::{QuizMsg, QuizView};
+use super::trash_view::{TrashMsg, TrashView};
 use crate::collection;
 
 pub type CollectionSender = mpsc::Sender<collection::Action>;
@@ -18,12 +23,82 @@ pub fn render(tx: CollectionSender, rx: UpdateReceiver) {
     Win::run((tx, rx)).expect("Win::run failed");
 }
 
+/// Shows a message that has to be seen before carrying on, e.g. when Ranki
+/// can't open a collection
+pub fn alert(message: &str) {
+    println!("error: {}", message);
+    if gtk::init().is_err() {
+        return;
+    }
+    let dialog = gtk::MessageDialog::new(
+        None::<&gtk::Window>,
+        gtk::DialogFlags::MODAL,
+        gtk::MessageType::Error,
+        gtk::ButtonsType::Ok,
+        message,
+    );
+    dialog.set_title("Ranki");
+    dialog.run();
+    dialog.close();
+}
+
+/// Asks for the passphrase of an encrypted collection, returning None if the
+/// question is dismissed. A new passphrase has to
+/// be typed twice, so a typo doesn't lock the collection away.
+pub fn ask_passphrase(new: bool) -> Option<String> {
+    if gtk::init().is_err() {
+        println!("error: failed to initialise GTK");
+        return None;
+    }
+    let dialog = gtk::Dialog::new();
+    dialog.set_title(if new { "Choose a Passphrase" } else { "Enter the Passphrase" });
+    dialog.set_border_width(10);
+    let content = dialog.get_content_area();
+    let passphrase = gtk::Entry::new();
+    passphrase.set_visibility(false);
+    passphrase.set_activates_default(true);
+    content.add(&passphrase);
+    let repeated = gtk::Entry::new();
+    repeated.set_visibility(false);
+    repeated.set_activates_default(true);
+    repeated.set_placeholder_text(Some("Again"));
+    if new {
+        content.add(&repeated);
+    }
+    let mismatch = gtk::Label::new(Some("The passphrases don't match."));
+    content.add(&mismatch);
+    dialog.add_button("Open", gtk::ResponseType::Ok);
+    dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+    dialog.set_default_response(gtk::ResponseType::Ok);
+    dialog.show_all();
+    mismatch.hide();
+
+    let chosen = loop {
+        if dialog.run() != gtk::ResponseType::Ok {
+            break None;
+        }
+        let text = passphrase.get_text().map(|text| text.to_string()).unwrap_or_default();
+        let again = repeated.get_text().map(|text| text.to_string()).unwrap_or_default();
+        if text.is_empty() || (new && text != again) {
+            mismatch.set_visible(!text.is_empty());
+            continue;
+        }
+        break Some(text);
+    };
+    dialog.close();
+    chosen
+}
+
 pub struct Model {
+    backups: Option<Component<BackupView>>,
+    browser: Option<Component<CardBrowser>>,
     card_view: Option<Component<CardView>>,
     collection: Rc<RefCell<collection::Collection>>,
+    profiles: Option<Component<ProfileView>>,
     tx: CollectionSender,
     selected_deck: Option<u32>,
     stream: EventStream<Msg>,
+    trash: Option<Component<TrashView>>,
     rx: Rc<UpdateReceiver>,
 }
 
@@ -32,13 +107,49 @@ pub enum Msg {
     AddDeck(String),
     DeleteDeck(u32),
     EditDeck(String),
-    AddCard(String, String),
-    DeleteCard(u32),
-    EditCard(u32, String, String),
+    AddFilteredDeck(String, String, bool),
+    RebuildDeck(u32),
+    EmptyDeck(u32),
+    AddCard(String, String, String),
+    DeleteCard(Uuid),
+    EditCard(Uuid, String, String, String),
+    RevertCard(Uuid, usize),
+    SetTags(Uuid, Vec<String>),
+    SetFlag(Uuid, Option<collection::Flag>),
+    SetMarked(Uuid, bool),
+    KeyPress(u32, gdk::ModifierType),
+    Undo,
+    Redo,
+    MergeCards(Vec<Uuid>),
+    FindReplace(collection::Replace),
+    OpenBrowser,
+    CloseBrowser,
+    RestoreDeck(u32),
+    RestoreCard(Uuid),
+    PurgeDeck(u32),
+    PurgeCard(Uuid),
+    EmptyTrash,
+    SetTrashRetention(u32),
+    OpenTrash,
+    CloseTrash,
+    BackUp,
+    RestoreBackup(String),
+    SetBackupSettings(collection::BackupSettings),
+    OpenBackups,
+    CloseBackups,
+    CreateProfile(String),
+    RenameProfile(u32, String),
+    DeleteProfile(u32),
+    SwitchProfile(u32),
+    OpenProfiles,
+    CloseProfiles,
+    CheckCollection,
     SelectedDeck(Option<u32>),
-    StartQuiz,
+    StartQuiz(Option<Vec<u32>>),
+    StartDeckQuiz,
+    StartCram(Vec<u32>, collection::CramOrder),
     Tick,
-    QuizComplete(Vec<(u32, u32, Option<bool>)>),
+    QuizComplete(Vec<(Uuid, Option<bool>)>),
     Quit,
 }
 
@@ -46,11 +157,15 @@ pub enum Msg {
 impl Widget for Win {
     fn model(relm: &Relm<Self>, (tx, rx): (CollectionSender, UpdateReceiver)) -> Model {
         Model {
+            backups: None,
+            browser: None,
             card_view: None,
             collection: Rc::new(RefCell::new(collection::Collection::new())),
+            profiles: None,
             tx,
             selected_deck: None,
             stream: relm.stream().clone(),
+            trash: None,
             rx: Rc::new(rx),
         }
     }
@@ -90,12 +205,39 @@ impl Widget for Win {
                     );
                 }
             },
-            Msg::AddCard(question, answer) => match self.model.selected_deck {
+            Msg::AddFilteredDeck(title, query, reschedule) => {
+                if let Err(e) = self.model.tx.send(collection::Action::AddFilteredDeck(
+                    title.clone(),
+                    query,
+                    reschedule,
+                )) {
+                    println!(
+                        "error sending add filtered deck msg to collection mgr. name {}, error {}",
+                        title, e
+                    );
+                }
+            }
+            Msg::RebuildDeck(id) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::RebuildFilteredDeck(id))
+                {
+                    println!("error sending rebuild deck msg to collection mgr: {}", e);
+                }
+            }
+            Msg::EmptyDeck(id) => {
+                if let Err(e) = self.model.tx.send(collection::Action::EmptyFilteredDeck(id)) {
+                    println!("error sending empty deck msg to collection mgr: {}", e);
+                }
+            }
+            Msg::AddCard(question, answer, note) => match self.model.selected_deck {
                 Some(deck_id) => {
                     if let Err(e) = self.model.tx.send(collection::Action::AddCard(
                         deck_id,
                         question.clone(),
                         answer.clone(),
+                        note,
                     )) {
                         println!(
                             "error sending add card msg to collection mgr. deck_id {} q {} a {} error {}",
@@ -107,44 +249,274 @@ impl Widget for Win {
                     println!("error: received add card msg when no deck active");
                 }
             },
-            Msg::DeleteCard(card_id) => match self.model.selected_deck {
-                Some(deck_id) => {
+            Msg::DeleteCard(uid) => {
+                if let Err(e) = self.model.tx.send(collection::Action::DeleteCard(uid)) {
+                    println!(
+                        "error sending delete card msg to collection mgr. card {} error {}",
+                        uid, e
+                    );
+                }
+            }
+            Msg::EditCard(uid, question, answer, note) => {
+                if let Err(e) = self.model.tx.send(collection::Action::EditCard(
+                    uid,
+                    question.clone(),
+                    answer.clone(),
+                    note,
+                )) {
+                    println!(
+                        "error sending edit card msg to collection mgr. card {} q{} a{} error {}",
+                        uid, question, answer, e
+                    );
+                }
+            }
+            Msg::SetTags(uid, tags) => {
+                if let Err(e) = self.model.tx.send(collection::Action::SetTags(uid, tags)) {
+                    println!(
+                        "error sending set tags msg to collection mgr. card {} error {}",
+                        uid, e
+                    );
+                }
+            }
+            Msg::SetFlag(uid, flag) => {
+                if let Err(e) = self.model.tx.send(collection::Action::SetFlag(uid, flag)) {
+                    println!(
+                        "error sending set flag msg to collection mgr. card {} error {}",
+                        uid, e
+                    );
+                }
+            }
+            Msg::SetMarked(uid, marked) => {
+                if let Err(e) = self.model.tx.send(collection::Action::SetMarked(uid, marked)) {
+                    println!(
+                        "error sending set marked msg to collection mgr. card {} error {}",
+                        uid, e
+                    );
+                }
+            }
+            Msg::KeyPress(keyval, modifiers) => {
+                // Shortcuts all use Ctrl, so typing into entries is left alone
+                if !modifiers.contains(gdk::ModifierType::CONTROL_MASK) {
+                    return;
+                }
+                let shift = modifiers.contains(gdk::ModifierType::SHIFT_MASK);
+                match std::char::from_u32(keyval).map(|c| c.to_ascii_lowercase()) {
+                    Some('z') if shift => self.model.stream.emit(Msg::Redo),
+                    Some('z') => self.model.stream.emit(Msg::Undo),
+                    _ => self.quiz_view.emit(QuizMsg::Key(keyval)),
+                }
+                return;
+            }
+            Msg::Undo => {
+                if let Err(e) = self.model.tx.send(collection::Action::Undo) {
+                    println!("error sending undo msg to collection mgr: {}", e);
+                }
+            }
+            Msg::Redo => {
+                if let Err(e) = self.model.tx.send(collection::Action::Redo) {
+                    println!("error sending redo msg to collection mgr: {}", e);
+                }
+            }
+            Msg::RevertCard(uid, revision) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::RevertCard(uid, revision))
+                {
+                    println!("error sending revert card msg to collection mgr: {}", e);
+                }
+            }
+            Msg::MergeCards(cards) => {
+                if let Err(e) = self.model.tx.send(collection::Action::MergeCards(cards)) {
+                    println!("error sending merge cards msg to collection mgr: {}", e);
+                }
+            }
+            Msg::FindReplace(replace) => {
+                if let Err(e) = self.model.tx.send(collection::Action::FindReplace(replace)) {
+                    println!("error sending find replace msg to collection mgr: {}", e);
+                }
+            }
+            Msg::OpenBrowser => {
+                // Only keep a single browser window around
+                if self.model.browser.is_some() {
+                    return;
+                }
+                match relm::init::<CardBrowser>((
+                    self.model.collection.clone(),
+                    self.model.stream.clone(),
+                )) {
+                    Ok(browser) => self.model.browser = Some(browser),
+                    Err(_) => println!("error: failed to open card browser"),
+                }
+            }
+            Msg::CloseBrowser => {
+                self.model.browser = None;
+            }
+            Msg::RestoreDeck(id) => {
+                if let Err(e) = self.model.tx.send(collection::Action::RestoreDeck(id)) {
+                    println!("error sending restore deck msg to collection mgr: {}", e);
+                }
+            }
+            Msg::RestoreCard(uid) => {
+                if let Err(e) = self.model.tx.send(collection::Action::RestoreCard(uid)) {
+                    println!("error sending restore card msg to collection mgr: {}", e);
+                }
+            }
+            Msg::PurgeDeck(id) => {
+                if let Err(e) = self.model.tx.send(collection::Action::PurgeDeck(id)) {
+                    println!("error sending purge deck msg to collection mgr: {}", e);
+                }
+            }
+            Msg::PurgeCard(uid) => {
+                if let Err(e) = self.model.tx.send(collection::Action::PurgeCard(uid)) {
+                    println!("error sending purge card msg to collection mgr: {}", e);
+                }
+            }
+            Msg::EmptyTrash => {
+                if let Err(e) = self.model.tx.send(collection::Action::EmptyTrash) {
+                    println!("error sending empty trash msg to collection mgr: {}", e);
+                }
+            }
+            Msg::SetTrashRetention(days) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::SetTrashRetention(days))
+                {
+                    println!("error sending trash retention msg to collection mgr: {}", e);
+                }
+            }
+            Msg::OpenTrash => {
+                if self.model.trash.is_some() {
+                    return;
+                }
+                match relm::init::<TrashView>((
+                    self.model.collection.clone(),
+                    self.model.stream.clone(),
+                )) {
+                    Ok(trash) => self.model.trash = Some(trash),
+                    Err(_) => println!("error: failed to open trash"),
+                }
+            }
+            Msg::CloseTrash => {
+                self.model.trash = None;
+            }
+            Msg::BackUp => {
+                if let Err(e) = self.model.tx.send(collection::Action::BackUp) {
+                    println!("error sending back up msg to collection mgr: {}", e);
+                }
+            }
+            Msg::RestoreBackup(name) => {
+                if let Err(e) = self.model.tx.send(collection::Action::RestoreBackup(name)) {
+                    println!("error sending restore backup msg to collection mgr: {}", e);
+                }
+            }
+            Msg::SetBackupSettings(settings) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::SetBackupSettings(settings))
+                {
+                    println!("error sending backup settings msg to collection mgr: {}", e);
+                }
+            }
+            Msg::OpenBackups => {
+                if self.model.backups.is_some() {
+                    return;
+                }
+                match relm::init::<BackupView>((
+                    self.model.collection.clone(),
+                    self.model.stream.clone(),
+                )) {
+                    Ok(backups) => self.model.backups = Some(backups),
+                    Err(_) => {
+                        println!("error: failed to open backups");
+                        return;
+                    }
+                }
+                if let Err(e) = self.model.tx.send(collection::Action::ListBackups) {
+                    println!("error sending list backups msg to collection mgr: {}", e);
+                }
+            }
+            Msg::CloseBackups => {
+                self.model.backups = None;
+            }
+            Msg::CreateProfile(name) => {
+                if let Err(e) = self.model.tx.send(collection::Action::CreateProfile(name)) {
+                    println!("error sending create profile msg to collection mgr: {}", e);
+                }
+            }
+            Msg::RenameProfile(id, name) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::RenameProfile(id, name))
+                {
+                    println!("error sending rename profile msg to collection mgr: {}", e);
+                }
+            }
+            Msg::DeleteProfile(id) => {
+                if let Err(e) = self.model.tx.send(collection::Action::DeleteProfile(id)) {
+                    println!("error sending delete profile msg to collection mgr: {}", e);
+                }
+            }
+            Msg::SwitchProfile(id) => {
+                if let Err(e) = self.model.tx.send(collection::Action::SwitchProfile(id, None)) {
+                    println!("error sending switch profile msg to collection mgr: {}", e);
+                }
+            }
+            Msg::OpenProfiles => {
+                if self.model.profiles.is_some() {
+                    return;
+                }
+                match relm::init::<ProfileView>((
+                    self.model.collection.clone(),
+                    self.model.stream.clone(),
+                )) {
+                    Ok(profiles) => self.model.profiles = Some(profiles),
+                    Err(_) => {
+                        println!("error: failed to open profiles");
+                        return;
+                    }
+                }
+                if let Err(e) = self.model.tx.send(collection::Action::ListProfiles) {
+                    println!("error sending list profiles msg to collection mgr: {}", e);
+                }
+            }
+            Msg::CloseProfiles => {
+                self.model.profiles = None;
+            }
+            Msg::CheckCollection => {
+                if let Err(e) = self.model.tx.send(collection::Action::CheckCollection) {
+                    println!("error sending check collection msg: {}", e);
+                }
+            }
+            Msg::StartQuiz(deck_ids) => {
+                if let Err(e) = self.model.tx.send(collection::Action::GetQuiz(deck_ids)) {
+                    println!("error sending get quiz msg: {}", e);
+                }
+            }
+            Msg::StartDeckQuiz => match self.model.selected_deck {
+                Some(id) => {
                     if let Err(e) = self
                         .model
                         .tx
-                        .send(collection::Action::DeleteCard(deck_id, card_id))
+                        .send(collection::Action::GetQuiz(Some(vec![id])))
                     {
-                        println!(
-                            "error sending delete card msg to collection mgr. deck_id {} card_id {} error {}",
-                            deck_id, card_id, e
-                        );
+                        println!("error sending get deck quiz msg: {}", e);
                     }
                 }
                 None => {
-                    println!("error: received delete card msg when no deck active");
+                    println!("error: received deck quiz msg when no deck active");
                 }
             },
-            Msg::EditCard(card_id, question, answer) => match self.model.selected_deck {
-                Some(deck_id) => {
-                    if let Err(e) = self.model.tx.send(collection::Action::EditCard(
-                        deck_id,
-                        card_id,
-                        question.clone(),
-                        answer.clone(),
-                    )) {
-                        println!(
-                            "error sending edit card msg to collection mgr. deck_id {} card_id {} q{} a{} error {}",
-                            deck_id, card_id, question, answer, e
-                        );
-                    }
-                }
-                None => {
-                    println!("error: received edit card msg when no deck active");
-                }
-            },
-            Msg::StartQuiz => {
-                if let Err(e) = self.model.tx.send(collection::Action::GetQuiz) {
-                    println!("error sending get quiz msg: {}", e);
+            Msg::StartCram(deck_ids, order) => {
+                if let Err(e) = self
+                    .model
+                    .tx
+                    .send(collection::Action::GetCram(deck_ids, order))
+                {
+                    println!("error sending get cram msg: {}", e);
                 }
             }
             Msg::SelectedDeck(id) => {
@@ -163,30 +535,119 @@ impl Widget for Win {
                     self.model.selected_deck = None;
                 }
             }
-            Msg::Tick => match self.model.rx.try_recv() {
-                Err(mpsc::TryRecvError::Empty) => {}
-                Ok(collection::SendData::Collection(collection)) => {
+            Msg::Tick => {
+                // Handle everything that arrived since the last tick, only
+                // redrawing for the newest of any collections sent
+                let mut newest = None;
+                loop {
+                    match self.model.rx.try_recv() {
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Ok(collection::SendData::Collection(collection)) => {
+                            newest = Some(collection);
+                        }
+                        Ok(collection::SendData::Quiz(quiz)) => {
+                            self.quiz_view.emit(QuizMsg::GotQuiz(quiz));
+                        }
+                        Ok(collection::SendData::Cram(quiz)) => {
+                            self.quiz_view.emit(QuizMsg::GotCram(quiz));
+                        }
+                        Ok(collection::SendData::History(undo, redo)) => {
+                            self.undo_button.set_sensitive(undo.is_some());
+                            self.undo_button.set_label(&match undo {
+                                Some(label) => format!("Undo {}", label),
+                                None => "Undo".to_string(),
+                            });
+                            self.redo_button.set_sensitive(redo.is_some());
+                            self.redo_button.set_label(&match redo {
+                                Some(label) => format!("Redo {}", label),
+                                None => "Redo".to_string(),
+                            });
+                        }
+                        Ok(collection::SendData::Backups(backups)) => {
+                            if let Some(view) = &self.model.backups {
+                                view.emit(BackupMsg::Listed(backups));
+                            }
+                        }
+                        Ok(collection::SendData::Profiles(profiles)) => {
+                            if let Some(view) = &self.model.profiles {
+                                view.emit(ProfileMsg::Listed(profiles));
+                            }
+                        }
+                        Ok(collection::SendData::Conflict) => {
+                            self.show_conflict();
+                        }
+                        Ok(collection::SendData::Check(report)) => {
+                            self.show_check(&report);
+                        }
+                        Ok(collection::SendData::PassphraseNeeded(id, wrong)) => {
+                            if wrong {
+                                alert("The passphrase is wrong, or the file has been damaged.");
+                            }
+                            // Cancelling leaves the open profile as it is
+                            if let Some(passphrase) = ask_passphrase(false) {
+                                let switch =
+                                    collection::Action::SwitchProfile(id, Some(passphrase));
+                                if let Err(e) = self.model.tx.send(switch) {
+                                    println!(
+                                        "error sending switch profile msg to collection mgr: {}",
+                                        e
+                                    );
+                                }
+                            }
+                        }
+                        Ok(collection::SendData::Error(message)) => {
+                            alert(&message);
+                        }
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            panic!("Window receiver disconnected");
+                        }
+                    }
+                }
+                if let Some(collection) = newest {
+                    self.window
+                        .set_title(&format!("anki-rs - {}", collection.owner));
+                    // Deck ids start over in every profile, so a deck picked
+                    // in the last one would stand for some other deck here
+                    let switched = collection.id != self.model.collection.borrow().id;
                     self.model.collection.replace(collection);
+                    if switched {
+                        if let Some(widget) = self.model.card_view.take() {
+                            self.card_view_box.remove_widget(widget);
+                        }
+                        self.model.selected_deck = None;
+                        self.deck_view.emit(DeckViewMsg::Cleared);
+                    }
                     self.deck_view.emit(DeckViewMsg::NewCollection);
+                    self.quiz_view.emit(QuizMsg::NewCollection);
                     if let Some(id) = self.model.selected_deck {
                         if let Some(card_view) = &self.model.card_view {
                             card_view.emit(CardViewMsg::UpdateToDeck(id));
                         }
                     }
+                    if let Some(browser) = &self.model.browser {
+                        browser.emit(CardBrowserMsg::Refresh);
+                    }
+                    if let Some(trash) = &self.model.trash {
+                        trash.emit(TrashMsg::Refresh);
+                    }
+                    if let Some(backups) = &self.model.backups {
+                        backups.emit(BackupMsg::Refresh);
+                    }
+                    if let Some(profiles) = &self.model.profiles {
+                        profiles.emit(ProfileMsg::Refresh);
+                    }
                 }
-                Ok(collection::SendData::Quiz(quiz)) => {
-                    self.quiz_view.emit(QuizMsg::GotQuiz(quiz));
-                }
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    panic!("Window receiver disconnected");
-                }
-            },
+            }
             Msg::QuizComplete(results) => {
                 if let Err(e) = self.model.tx.send(collection::Action::QuizResults(results)) {
                     println!("error sending quiz results to collection mgr. error: {}", e);
                 }
             }
             Msg::Quit => {
+                // Let the collection service take a last backup and finish
+                if let Err(e) = self.model.tx.send(collection::Action::Quit) {
+                    println!("error sending quit msg to collection mgr: {}", e);
+                }
                 gtk::main_quit();
             }
         }
@@ -201,6 +662,72 @@ impl Widget for Win {
         self.window.resize(1, 1);
     }
 
+    // Asks whether to take the collection as something else saved it, or keep
+    // the changes made here that couldn't be saved. Answered outside of the
+    // usual messages, as the dialog can outlive the tick that opened it.
+    fn show_conflict(&self) {
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            gtk::MessageType::Warning,
+            gtk::ButtonsType::None,
+            "The collection was changed outside of Ranki while changes made here \
+             were still unsaved. Reload it, losing those changes, or keep them and \
+             overwrite the outside changes?",
+        );
+        dialog.add_button("Reload", gtk::ResponseType::Reject);
+        dialog.add_button("Keep My Changes", gtk::ResponseType::Accept);
+        let tx = self.model.tx.clone();
+        dialog.connect_response(move |dialog, response| {
+            let action = match response {
+                gtk::ResponseType::Reject => collection::Action::ReloadCollection,
+                _ => collection::Action::KeepCollection,
+            };
+            if let Err(e) = tx.send(action) {
+                println!("error sending conflict answer to collection mgr: {}", e);
+            }
+            dialog.close();
+        });
+        dialog.show_all();
+    }
+
+    fn show_check(&self, report: &collection::CheckReport) {
+        let mut text = String::new();
+        if report.is_clean() {
+            text.push_str("No problems found.");
+        }
+        if !report.repaired.is_empty() {
+            text.push_str("Repaired, which can be undone:\n");
+            for repaired in &report.repaired {
+                text.push_str(&format!("• {}\n", repaired));
+            }
+        }
+        if !report.problems.is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str("Left for you to fix:\n");
+            for problem in &report.problems {
+                text.push_str(&format!("• {}\n", problem));
+            }
+        }
+        let kind = if report.problems.is_empty() {
+            gtk::MessageType::Info
+        } else {
+            gtk::MessageType::Warning
+        };
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.window),
+            gtk::DialogFlags::DESTROY_WITH_PARENT,
+            kind,
+            gtk::ButtonsType::Ok,
+            text.trim_end(),
+        );
+        dialog.set_title("Check Collection");
+        dialog.connect_response(|dialog, _| dialog.close());
+        dialog.show_all();
+    }
+
     view! {
         #[name="window"]
         gtk::Window {
@@ -209,13 +736,55 @@ impl Widget for Win {
             position: gtk::WindowPosition::Center,
             gtk::Box {
                 orientation: gtk::Orientation::Vertical,
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    #[name="undo_button"]
+                    gtk::Button {
+                        label: "Undo",
+                        sensitive: false,
+                        clicked => Msg::Undo,
+                    },
+                    #[name="redo_button"]
+                    gtk::Button {
+                        label: "Redo",
+                        sensitive: false,
+                        clicked => Msg::Redo,
+                    },
+                },
                 #[name="deck_view"]
                 DeckView(self.model.collection.clone(), self.model.stream.clone()),
+                gtk::Box {
+                    orientation: gtk::Orientation::Horizontal,
+                    gtk::Button {
+                        label: "Browse Cards",
+                        clicked => Msg::OpenBrowser,
+                    },
+                    gtk::Button {
+                        label: "Trash",
+                        clicked => Msg::OpenTrash,
+                    },
+                    gtk::Button {
+                        label: "Backups",
+                        clicked => Msg::OpenBackups,
+                    },
+                    gtk::Button {
+                        label: "Profiles",
+                        clicked => Msg::OpenProfiles,
+                    },
+                    gtk::Button {
+                        label: "Check Collection",
+                        clicked => Msg::CheckCollection,
+                    },
+                },
                 #[name="card_view_box"]
                 gtk::Box {},
                 #[name="quiz_view"]
-                QuizView(self.model.stream.clone()),
+                QuizView(self.model.collection.clone(), self.model.stream.clone()),
             },
+            key_press_event(_, key) => (
+                Msg::KeyPress(key.get_keyval(), key.get_state()),
+                Inhibit(false)
+            ),
             delete_event(_, _) => (Msg::Quit, Inhibit(false)),
         }
     }
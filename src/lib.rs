@@ -0,0 +1,4 @@
+//! The collection and everything done with it, apart from the GUI, so that
+//! tests and benches can use it too.
+
+pub mod collection;
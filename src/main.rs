@@ -1,21 +1,138 @@
+use std::env;
 use std::error;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
 
-mod collection;
+use ranki::collection;
+
 mod gui;
 
 fn main() -> Result<(), Box<dyn error::Error>> {
     let (action_tx, action_rx) = mpsc::channel();
     let (update_tx, update_rx) = mpsc::channel();
 
-    let mut collection_service = collection::CollectionService::new(update_tx)?;
+    // How to keep the collection, from `--storage <kind>` or RANKI_STORAGE,
+    // and where, from `--collection <dir>` or RANKI_COLLECTION
+    let args: Vec<String> = env::args().collect();
+    let kind = arg_or_var(&args, "--storage", "RANKI_STORAGE");
+    let custom_dir = arg_or_var(&args, "--collection", "RANKI_COLLECTION");
+    let dir = match &custom_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => collection::storage::default_dir()?,
+    };
+
+    // Held until Ranki exits, so a second one can't open the same collections
+    let _lock = match collection::Lock::acquire(&dir) {
+        Ok(lock) => lock,
+        Err(e) => {
+            gui::alert(&e.to_string());
+            return Err(e);
+        }
+    };
+
+    // Which profile to open, from `--profile <name>` or RANKI_PROFILE, or else
+    // the one used last. Failing that, ask.
+    let profiles = collection::Profiles::open(&dir)?;
+    // Older versions kept the collection in the directory Ranki was started from
+    if custom_dir.is_none() {
+        profiles.adopt(Path::new(""))?;
+    }
+    let profile = match arg_or_var(&args, "--profile", "RANKI_PROFILE") {
+        Some(name) => match profiles.by_name(&name) {
+            Some(profile) => profile.id,
+            None => return Err(format!("no profile called {}", name).into()),
+        },
+        None => match profiles.last() {
+            Some(id) => id,
+            None => match gui::choose_profile(profiles.list()) {
+                Some(id) => id,
+                None => return Ok(()),
+            },
+        },
+    };
 
-    thread::spawn(move || {
+    // `--encrypt` and `--decrypt` turn encryption of the profile on and off.
+    // Either one, or a collection that's already encrypted, needs the
+    // passphrase, from RANKI_PASSPHRASE or else asked for until it's right.
+    let encrypt = args.iter().any(|arg| arg == "--encrypt");
+    let decrypt = args.iter().any(|arg| arg == "--decrypt");
+    let encrypted = collection::storage::is_encrypted(&profiles.dir_of(profile));
+    let from_var = env::var("RANKI_PASSPHRASE").ok();
+    let mut profiles = Some(profiles);
+    let mut collection_service = loop {
+        let passphrase = match &from_var {
+            _ if !encrypt && !encrypted => None,
+            Some(passphrase) => Some(passphrase.clone()),
+            None => match gui::ask_passphrase(!encrypted) {
+                Some(passphrase) => Some(passphrase),
+                None => return Ok(()),
+            },
+        };
+        let profiles = match profiles.take() {
+            Some(profiles) => profiles,
+            None => collection::Profiles::open(&dir)?,
+        };
+        match collection::CollectionService::new(
+            update_tx.clone(),
+            kind.clone(),
+            passphrase,
+            profiles,
+            profile,
+        ) {
+            Ok(service) => break service,
+            Err(e) if from_var.is_none() && e.is::<collection::WrongPassphrase>() => {
+                gui::alert(&e.to_string())
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    if encrypt || decrypt {
+        collection_service.set_encrypted(encrypt)?;
+    }
+    // `--format <json|compact>` rewrites the profile in that format
+    if let Some(format) = arg(&args, "--format") {
+        collection_service.set_format(collection::storage::Format::parse(&format)?)?;
+    }
+    // `--check` checks the profile, repairing what it can, and exits
+    if args.iter().any(|arg| arg == "--check") {
+        let report = collection_service.check()?;
+        for repaired in &report.repaired {
+            println!("Repaired: {}", repaired);
+        }
+        for problem in &report.problems {
+            println!("Problem: {}", problem);
+        }
+        if report.is_clean() {
+            println!("No problems found.");
+        }
+        return Ok(());
+    }
+
+    let service = thread::spawn(move || {
         collection_service.listen(action_rx);
     });
 
     gui::render(action_tx, update_rx);
 
+    // Let the service finish saving and backing up before exiting
+    if service.join().is_err() {
+        println!("error: collection service stopped unexpectedly");
+    }
+
     Ok(())
 }
+
+// The value following a command line flag
+fn arg(args: &[String], flag: &str) -> Option<String> {
+    let pos = args.iter().position(|arg| arg == flag)?;
+    args.get(pos + 1).cloned()
+}
+
+// The value following a command line flag, or else an environment variable
+fn arg_or_var(args: &[String], flag: &str, var: &str) -> Option<String> {
+    if args.iter().any(|arg| arg == flag) {
+        return arg(args, flag);
+    }
+    env::var(var).ok()
+}
@@ -0,0 +1,132 @@
+//! Taking backups and thinning out the old ones, keeping the newest few and
+//! then one a day and one a week.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ranki::collection::{BackupSettings, Backups, Collection};
+
+mod common;
+
+use common::TempDir;
+
+const DAY: u64 = 86400;
+const WEEK: u64 = DAY * 7;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock after the epoch")
+        .as_secs()
+}
+
+// Leaves backups named as if taken at the given seconds since the epoch. Only
+// their names matter for thinning.
+fn old_backups(dir: &Path, taken: &[u64]) {
+    fs::create_dir_all(dir).expect("creating backups directory");
+    for secs in taken {
+        fs::write(dir.join(format!("backup-{}.000.json", secs)), "{}").expect("writing backup");
+    }
+}
+
+// Backs up a collection with the given settings, returning when each backup
+// left behind was taken, leaving out the new one
+fn back_up(dir: &Path, settings: BackupSettings) -> Vec<u64> {
+    let mut collection = Collection::new();
+    collection.backup_settings = settings;
+    let mut backups = Backups::new(dir);
+    backups.write(&collection).expect("taking backup");
+
+    let mut left: Vec<u64> = fs::read_dir(dir)
+        .expect("listing backups")
+        .map(|entry| entry.expect("reading entry").file_name().to_string_lossy().to_string())
+        .filter_map(|name| {
+            let secs = name.strip_prefix("backup-")?.split('.').next()?;
+            secs.parse().ok()
+        })
+        .collect();
+    left.sort();
+    left.reverse();
+    assert!(left.remove(0) >= now() - 60, "new backup taken");
+    left
+}
+
+#[test]
+fn newest_backups_are_kept_however_old() {
+    let dir = TempDir::new("backups-keep");
+    let old = now() - 100 * DAY;
+    old_backups(&dir, &[old, old + 1, old + 2, old + 3]);
+    let settings = BackupSettings {
+        keep: 3,
+        interval_minutes: 30,
+        daily: 0,
+        weekly: 0,
+    };
+
+    assert_eq!(back_up(&dir, settings), [old + 3, old + 2]);
+}
+
+#[test]
+fn the_newest_backup_of_each_day_is_kept() {
+    let dir = TempDir::new("backups-daily");
+    let yesterday = (now() / DAY - 1) * DAY;
+    let before = yesterday - DAY;
+    old_backups(&dir, &[before + 100, yesterday + 100, yesterday + 200]);
+    let settings = BackupSettings {
+        keep: 1,
+        interval_minutes: 30,
+        daily: 7,
+        weekly: 0,
+    };
+
+    assert_eq!(back_up(&dir, settings), [yesterday + 200, before + 100]);
+}
+
+#[test]
+fn the_newest_backup_of_each_week_is_kept_after_the_days() {
+    let dir = TempDir::new("backups-weekly");
+    let week = (now() / WEEK - 2) * WEEK;
+    let ancient = now() - 100 * DAY;
+    old_backups(&dir, &[ancient, week + 100, week + 200]);
+    let settings = BackupSettings {
+        keep: 1,
+        interval_minutes: 30,
+        daily: 3,
+        weekly: 4,
+    };
+
+    assert_eq!(back_up(&dir, settings), [week + 200]);
+}
+
+#[test]
+fn files_not_named_as_backups_are_left_alone() {
+    let dir = TempDir::new("backups-others");
+    old_backups(&dir, &[now() - 100 * DAY]);
+    fs::write(dir.join("notes.txt"), "mine").expect("writing file");
+    let settings = BackupSettings {
+        keep: 1,
+        interval_minutes: 30,
+        daily: 0,
+        weekly: 0,
+    };
+
+    assert!(back_up(&dir, settings).is_empty());
+    assert!(dir.join("notes.txt").exists());
+}
+
+#[test]
+fn backups_are_due_after_the_interval() {
+    let dir = TempDir::new("backups-due");
+    let settings = BackupSettings::default();
+    let mut backups = Backups::new(dir.to_path_buf());
+    assert!(backups.due(&settings));
+
+    backups.write(&Collection::new()).expect("taking backup");
+    assert!(!backups.due(&settings));
+    let every_time = BackupSettings {
+        interval_minutes: 0,
+        ..BackupSettings::default()
+    };
+    assert!(backups.due(&every_time));
+}
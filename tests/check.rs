@@ -0,0 +1,263 @@
+//! Checking collections that break the invariants the rest of the code relies
+//! on, as a hand-edited or half-synced file might. Each starts from a sound
+//! collection and breaks one thing in its saved form.
+
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use ranki::collection::{schema, CheckReport, Collection};
+
+const SPANISH: u32 = 0;
+const FRENCH: u32 = 1;
+const GERMAN: u32 = 2;
+const CRAM: u32 = 3;
+
+// Spanish has lent both its cards to the Cram filtered deck, and French has
+// one card of three in the trash
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    for title in ["Spanish", "French", "German"] {
+        collection.add_deck(title);
+    }
+    let cards = [
+        (SPANISH, "uno", "one"),
+        (SPANISH, "dos", "two"),
+        (FRENCH, "un", "one"),
+        (FRENCH, "deux", "two"),
+        (FRENCH, "trois", "three"),
+        (GERMAN, "eins", "one"),
+    ];
+    for (deck_id, question, answer) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), answer.to_string(), "".to_string())
+            .expect("adding card");
+    }
+    let trois = collection.decks[FRENCH as usize].card(2).expect("card is there").uid;
+    collection.trash_card(trois).expect("trashing card");
+    let cram = collection
+        .add_filtered_deck("Cram", "deck:Spanish", true)
+        .expect("adding filtered deck");
+    assert_eq!(cram, CRAM);
+    collection
+}
+
+// The sample, saved, changed and loaded back
+fn broken(change: impl FnOnce(&mut Value)) -> Collection {
+    let mut value = serde_json::to_value(sample()).expect("saving collection");
+    change(&mut value);
+    schema::load(value).expect("loading collection")
+}
+
+fn deck(collection: &Collection, title: &str) -> usize {
+    collection
+        .decks
+        .iter()
+        .position(|deck| deck.title == title)
+        .expect("deck is there")
+}
+
+fn card_ids(collection: &Collection, title: &str) -> Vec<u32> {
+    let deck = &collection.decks[deck(collection, title)];
+    deck.cards.iter().map(|card| card.id).collect()
+}
+
+// Checks the collection, expecting only repairs, after which it's sound
+fn repaired(collection: &mut Collection) -> CheckReport {
+    let report = collection.check();
+    assert!(!report.repaired.is_empty(), "nothing repaired");
+    assert!(report.problems.is_empty(), "problems: {:?}", report.problems);
+    assert!(collection.check().is_clean(), "second check wasn't clean");
+    report
+}
+
+// Checks the collection, expecting a single problem and nothing repaired
+fn problem(collection: &mut Collection) -> String {
+    let mut report = collection.check();
+    assert!(report.repaired.is_empty(), "repaired: {:?}", report.repaired);
+    assert_eq!(report.problems.len(), 1, "problems: {:?}", report.problems);
+    report.problems.remove(0)
+}
+
+#[test]
+fn sound_collection_is_clean() {
+    assert!(sample().check().is_clean());
+}
+
+#[test]
+fn shared_deck_id_is_renumbered() {
+    let mut collection = broken(|value| value["decks"][GERMAN as usize]["id"] = json!(CRAM));
+    repaired(&mut collection);
+
+    let ids: HashSet<u32> = collection.decks.iter().map(|deck| deck.id).collect();
+    assert_eq!(ids.len(), collection.decks.len());
+    assert_eq!(card_ids(&collection, "German"), [0]);
+}
+
+#[test]
+fn shared_deck_id_that_cards_refer_to_is_reported() {
+    let mut collection = broken(|value| value["decks"][GERMAN as usize]["id"] = json!(SPANISH));
+    problem(&mut collection);
+
+    assert_eq!(collection.decks[deck(&collection, "German")].id, SPANISH);
+    assert_eq!(collection.decks[deck(&collection, "Spanish")].id, SPANISH);
+}
+
+#[test]
+fn deck_id_counter_behind_is_moved_on() {
+    let mut collection = broken(|value| value["curr_deck_id"] = json!(GERMAN));
+    repaired(&mut collection);
+
+    collection.add_deck("Italian");
+    let ids: HashSet<u32> = collection.decks.iter().map(|deck| deck.id).collect();
+    assert_eq!(ids.len(), collection.decks.len());
+}
+
+#[test]
+fn deck_id_at_the_limit_is_reported() {
+    let mut collection = broken(|value| value["decks"][GERMAN as usize]["id"] = json!(u32::MAX));
+    let problem = problem(&mut collection);
+
+    assert!(problem.contains("no more decks"), "{}", problem);
+    assert_eq!(collection.decks[deck(&collection, "German")].id, u32::MAX);
+}
+
+#[test]
+fn card_ids_at_the_limit_are_reported() {
+    let mut collection = broken(|value| {
+        value["decks"][FRENCH as usize]["cards"][0]["id"] = json!(u32::MAX);
+        value["decks"][FRENCH as usize]["cards"][1]["id"] = json!(u32::MAX);
+    });
+    let report = collection.check();
+
+    assert!(report.repaired.is_empty(), "repaired: {:?}", report.repaired);
+    assert_eq!(report.problems.len(), 2, "problems: {:?}", report.problems);
+    assert_eq!(card_ids(&collection, "French"), [u32::MAX, u32::MAX]);
+}
+
+#[test]
+fn card_id_counter_behind_is_moved_past_trashed_cards() {
+    let mut collection =
+        broken(|value| value["decks"][FRENCH as usize]["curr_card_id"] = json!(1));
+    repaired(&mut collection);
+
+    collection
+        .add_card(FRENCH, "quatre".to_string(), "four".to_string(), "".to_string())
+        .expect("adding card");
+    assert_eq!(card_ids(&collection, "French"), [0, 1, 3]);
+}
+
+#[test]
+fn shared_card_id_is_renumbered() {
+    let mut collection =
+        broken(|value| value["decks"][FRENCH as usize]["cards"][1]["id"] = json!(0));
+    repaired(&mut collection);
+
+    assert_eq!(card_ids(&collection, "French"), [0, 3]);
+}
+
+#[test]
+fn card_id_held_by_trashed_card_is_renumbered() {
+    let mut collection =
+        broken(|value| value["decks"][FRENCH as usize]["cards"][1]["id"] = json!(2));
+    repaired(&mut collection);
+
+    assert_eq!(card_ids(&collection, "French"), [0, 3]);
+    let trashed = collection.trash.cards[0].card.uid;
+    collection.restore_card(trashed).expect("restoring card");
+    assert_eq!(card_ids(&collection, "French"), [0, 2, 3]);
+}
+
+#[test]
+fn borrowed_cards_with_the_same_home_go_back_as_different_cards() {
+    let mut collection = broken(|value| {
+        value["decks"][CRAM as usize]["cards"][1]["home"] = json!([SPANISH, 0]);
+    });
+    repaired(&mut collection);
+
+    collection.empty_filtered_deck(CRAM).expect("emptying filtered deck");
+    let ids = card_ids(&collection, "Spanish");
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[test]
+fn trashed_card_with_the_home_of_a_borrowed_one_is_renumbered() {
+    let mut collection = broken(|value| {
+        value["trash"]["cards"][0]["deck_id"] = json!(SPANISH);
+        value["trash"]["cards"][0]["card"]["id"] = json!(0);
+    });
+    repaired(&mut collection);
+
+    collection.empty_filtered_deck(CRAM).expect("emptying filtered deck");
+    let trashed = collection.trash.cards[0].card.uid;
+    collection.restore_card(trashed).expect("restoring card");
+    assert_eq!(card_ids(&collection, "Spanish"), [0, 1, 2]);
+}
+
+#[test]
+fn shared_uids_are_replaced() {
+    let mut collection = broken(|value| {
+        let uid = value["decks"][SPANISH as usize]["uid"].clone();
+        value["decks"][GERMAN as usize]["uid"] = uid.clone();
+        value["decks"][GERMAN as usize]["cards"][0]["uid"] = uid;
+    });
+    repaired(&mut collection);
+
+    let mut uids = HashSet::new();
+    for deck in &collection.decks {
+        assert!(uids.insert(deck.uid));
+        for card in &deck.cards {
+            assert!(uids.insert(card.uid));
+        }
+    }
+}
+
+#[test]
+fn card_at_home_marked_as_borrowed_is_unmarked() {
+    let mut collection = broken(|value| {
+        value["decks"][GERMAN as usize]["cards"][0]["home"] = json!([SPANISH, 5]);
+    });
+    repaired(&mut collection);
+
+    assert_eq!(collection.decks[deck(&collection, "German")].cards[0].home_deck(), None);
+}
+
+#[test]
+fn borrowed_card_from_missing_deck_is_reported() {
+    let mut collection =
+        broken(|value| value["decks"][CRAM as usize]["cards"][0]["home"] = json!([9, 0]));
+    assert!(problem(&mut collection).contains("doesn't exist"));
+}
+
+#[test]
+fn filtered_card_without_home_is_reported() {
+    let mut collection =
+        broken(|value| value["decks"][CRAM as usize]["cards"][0]["home"] = Value::Null);
+    assert!(problem(&mut collection).contains("wasn't borrowed"));
+}
+
+#[test]
+fn deck_without_title_is_reported() {
+    let mut collection = broken(|value| value["decks"][GERMAN as usize]["title"] = json!(" "));
+    assert!(problem(&mut collection).contains("no title"));
+}
+
+#[test]
+fn card_without_question_or_answer_is_reported() {
+    let mut collection =
+        broken(|value| value["decks"][GERMAN as usize]["cards"][0]["question"] = json!(""));
+    assert!(problem(&mut collection).contains("no question"));
+
+    let mut collection =
+        broken(|value| value["decks"][GERMAN as usize]["cards"][0]["answer"] = json!(""));
+    assert!(problem(&mut collection).contains("no answer"));
+}
+
+#[test]
+fn filtered_deck_with_broken_search_is_reported() {
+    let mut collection = broken(|value| {
+        value["decks"][CRAM as usize]["filter"]["query"] = json!("flag:purple");
+    });
+    assert!(problem(&mut collection).contains("can't be rebuilt"));
+}
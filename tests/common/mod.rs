@@ -0,0 +1,55 @@
+//! Helpers shared by the integration tests. Each test file uses only some of
+//! them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use ranki::collection::storage::{JsonStore, Storage};
+use ranki::collection::Collection;
+
+/// A fresh directory of its own for a test, removed again once the test is
+/// done with it
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("ranki-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("creating test directory");
+        TempDir(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Saves a collection holding one empty deck to the given file, making its
+/// directory if need be
+pub fn save_collection(path: &Path, deck: &str) {
+    let mut collection = Collection::new();
+    collection.add_deck(deck);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).expect("creating collection directory");
+    }
+    JsonStore::new(path).save(&collection).expect("saving collection");
+}
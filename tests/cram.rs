@@ -0,0 +1,99 @@
+//! Cramming, which asks every card in the ticked decks whether it's due or
+//! not and leaves their schedules alone.
+
+use ranki::collection::{Collection, CramOrder};
+
+// Spanish and French decks, with every Spanish card already answered so that
+// only the French card is due
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    for question in ["uno", "dos"] {
+        collection
+            .add_card(0, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+    }
+    collection
+        .add_card(1, "un".to_string(), "-".to_string(), "".to_string())
+        .expect("adding card");
+    let answered = collection.decks[0]
+        .cards
+        .iter()
+        .map(|card| (card.uid, Some(true)))
+        .collect();
+    collection.process_results(answered).expect("answering cards");
+    collection
+}
+
+fn questions(collection: &Collection, deck_ids: &[u32], order: CramOrder) -> Vec<String> {
+    collection
+        .get_cram(deck_ids, order)
+        .into_iter()
+        .map(|card| card.question)
+        .collect()
+}
+
+#[test]
+fn cramming_asks_cards_that_are_not_due() {
+    let collection = sample();
+    assert_eq!(collection.due_count(0), 0);
+    assert_eq!(questions(&collection, &[0], CramOrder::Added), ["uno", "dos"]);
+}
+
+#[test]
+fn cramming_sticks_to_the_ticked_decks() {
+    let collection = sample();
+    assert_eq!(questions(&collection, &[1], CramOrder::Added), ["un"]);
+    assert_eq!(
+        questions(&collection, &[1, 0], CramOrder::Added),
+        ["uno", "dos", "un"]
+    );
+    assert!(questions(&collection, &[], CramOrder::Added).is_empty());
+}
+
+#[test]
+fn random_order_asks_every_card_once() {
+    let collection = sample();
+    let mut asked = questions(&collection, &[0, 1], CramOrder::Random);
+    asked.sort();
+    assert_eq!(asked, ["dos", "un", "uno"]);
+}
+
+#[test]
+fn hardest_order_asks_the_shortest_intervals_first() {
+    let collection = sample();
+    assert_eq!(
+        questions(&collection, &[0, 1], CramOrder::Hardest),
+        ["un", "uno", "dos"]
+    );
+}
+
+#[test]
+fn answers_in_a_cramming_filtered_deck_leave_schedules_alone() {
+    let mut collection = sample();
+    let cram = collection
+        .add_filtered_deck("Cram", "", false)
+        .expect("adding filtered deck");
+    let pos = collection.deck_pos_by_id(cram).expect("deck is there");
+    let due: Vec<_> = collection.decks[pos]
+        .cards
+        .iter()
+        .map(|card| card.due())
+        .collect();
+    assert_eq!(collection.due_count(cram), 3);
+
+    let answered = collection.decks[pos]
+        .cards
+        .iter()
+        .map(|card| (card.uid, Some(false)))
+        .collect();
+    collection.process_results(answered).expect("answering cards");
+    let after: Vec<_> = collection.decks[pos]
+        .cards
+        .iter()
+        .map(|card| card.due())
+        .collect();
+    assert_eq!(after, due);
+    assert!(collection.decks[pos].cards.iter().all(|card| card.last_failed().is_none()));
+}
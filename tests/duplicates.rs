@@ -0,0 +1,102 @@
+//! Finding cards that share a question, however it's spaced or capitalised,
+//! and merging them into one.
+
+use uuid::Uuid;
+
+use ranki::collection::Collection;
+
+// Spanish and French each have a card asking "one", spaced and capitalised
+// differently, and Spanish has two cards asking "two"
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    let cards = [
+        (0, "one", "uno", "masculine"),
+        (0, "two", "dos", ""),
+        (0, "Two ", "dos", "as in dos equis"),
+        (0, "three", "tres", ""),
+        (1, "  ONE", "un", "feminine is une"),
+    ];
+    for (deck_id, question, answer, note) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), answer.to_string(), note.to_string())
+            .expect("adding card");
+    }
+    collection
+}
+
+fn uid(collection: &Collection, deck: usize, card: usize) -> Uuid {
+    collection.decks[deck].cards[card].uid
+}
+
+fn card_count(collection: &Collection) -> usize {
+    collection.decks.iter().map(|deck| deck.cards.len()).sum()
+}
+
+#[test]
+fn duplicates_are_grouped_across_decks_in_collection_order() {
+    let collection = sample();
+    let groups = collection.find_duplicates();
+    assert_eq!(
+        groups,
+        [
+            vec![uid(&collection, 0, 0), uid(&collection, 1, 0)],
+            vec![uid(&collection, 0, 1), uid(&collection, 0, 2)],
+        ]
+    );
+}
+
+#[test]
+fn questions_are_found_however_they_are_written() {
+    let collection = sample();
+    let found = collection.find_question("  one ");
+    assert_eq!(found, [uid(&collection, 0, 0), uid(&collection, 1, 0)]);
+    assert!(collection.find_question("four").is_empty());
+}
+
+#[test]
+fn merging_keeps_the_most_studied_card() {
+    let mut collection = sample();
+    let (spanish, french) = (uid(&collection, 0, 0), uid(&collection, 1, 0));
+    collection
+        .set_tags(spanish, vec!["numbers".to_string()])
+        .expect("tagging card");
+    collection
+        .set_tags(french, vec!["numbers".to_string(), "french".to_string()])
+        .expect("tagging card");
+    collection
+        .process_results(vec![(french, Some(true))])
+        .expect("answering card");
+
+    collection.merge_cards(&[spanish, french]).expect("merging cards");
+
+    assert!(collection.card_by_uid(spanish).is_none());
+    let (deck, kept) = collection.card_by_uid(french).expect("studied card is kept");
+    assert_eq!(deck.title, "French");
+    assert_eq!(kept.tags, ["numbers", "french"]);
+    assert_eq!(kept.note, "feminine is une\nmasculine");
+    assert_eq!(card_count(&collection), 4);
+}
+
+#[test]
+fn merging_with_a_missing_card_changes_nothing() {
+    let mut collection = sample();
+    let group = [uid(&collection, 0, 1), uid(&collection, 0, 2), Uuid::new_v4()];
+
+    assert!(collection.merge_cards(&group).is_err());
+    assert_eq!(card_count(&collection), 5);
+    assert_eq!(collection.find_duplicates().len(), 2);
+}
+
+#[test]
+fn cards_given_twice_are_merged_once() {
+    let mut collection = sample();
+    let (first, second) = (uid(&collection, 0, 1), uid(&collection, 0, 2));
+
+    collection
+        .merge_cards(&[first, second, second])
+        .expect("merging cards");
+    assert_eq!(card_count(&collection), 4);
+    assert_eq!(collection.find_duplicates().len(), 1);
+}
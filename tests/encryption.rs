@@ -0,0 +1,100 @@
+//! Encrypting and decrypting a profile along with the backups already taken.
+
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+
+use ranki::collection::{Action, CollectionService, Profiles, SendData};
+
+mod common;
+
+use common::TempDir;
+
+// Whether each backup in the directory is encrypted, as the file starts
+fn backups_encrypted(dir: &Path) -> Vec<bool> {
+    fs::read_dir(dir)
+        .expect("listing backups")
+        .map(|entry| {
+            let bytes = fs::read(entry.expect("reading entry").path()).expect("reading backup");
+            bytes.starts_with(b"ranki encrypted")
+        })
+        .collect()
+}
+
+#[test]
+fn backups_follow_the_collection_in_and_out_of_encryption() {
+    let data = TempDir::new("encrypt-backups");
+    let profiles = Profiles::open(&data).expect("opening profiles");
+    let profile = profiles.last().expect("default profile");
+    let backups = profiles.dir_of(profile).join("backups");
+    let (tx, _rx) = mpsc::channel();
+    let mut service = CollectionService::new(
+        tx,
+        Some("json".to_string()),
+        Some("passphrase".to_string()),
+        profiles,
+        profile,
+    )
+    .expect("opening collection");
+    assert_eq!(backups_encrypted(&backups), [false]);
+
+    service.set_encrypted(true).expect("encrypting");
+    assert_eq!(backups_encrypted(&backups), [true]);
+
+    service.set_encrypted(false).expect("decrypting");
+    assert_eq!(backups_encrypted(&backups), [false]);
+}
+
+#[test]
+fn switching_to_a_profile_with_another_passphrase_asks_for_it() {
+    let data = TempDir::new("encrypt-switch");
+    let mut profiles = Profiles::open(&data).expect("opening profiles");
+    let first = profiles.last().expect("default profile");
+    let other = profiles.create("Other").expect("creating profile");
+    let (tx, rx) = mpsc::channel();
+    let mut service = CollectionService::new(
+        tx.clone(),
+        Some("json".to_string()),
+        Some("other passphrase".to_string()),
+        Profiles::open(&data).expect("reopening profiles"),
+        other,
+    )
+    .expect("opening other profile");
+    service.set_encrypted(true).expect("encrypting");
+    let mut service = CollectionService::new(
+        tx,
+        Some("json".to_string()),
+        Some("passphrase".to_string()),
+        Profiles::open(&data).expect("reopening profiles"),
+        first,
+    )
+    .expect("opening first profile");
+
+    let (actions, listened) = mpsc::channel();
+    for action in [
+        Action::SwitchProfile(other, None),
+        Action::SwitchProfile(other, Some("wrong".to_string())),
+        Action::SwitchProfile(other, Some("other passphrase".to_string())),
+        Action::Quit,
+    ] {
+        actions.send(action).expect("sending action");
+    }
+    service.listen(listened);
+
+    let replies: Vec<_> = rx
+        .try_iter()
+        .filter_map(|data| match data {
+            SendData::PassphraseNeeded(id, wrong) => Some(format!("{} {}", id, wrong)),
+            SendData::Collection(collection) => Some(collection.owner),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        replies[replies.len() - 3..],
+        [
+            format!("{} false", other),
+            format!("{} true", other),
+            "Other".to_string()
+        ]
+    );
+}
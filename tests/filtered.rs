@@ -0,0 +1,135 @@
+//! Filtered decks, which borrow the cards matching a search from their home
+//! decks and hand them back when emptied, rebuilt or deleted.
+
+use ranki::collection::Collection;
+
+const SPANISH: u32 = 0;
+const FRENCH: u32 = 1;
+
+// Spanish and French decks, each with a verb tagged as one
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    let cards = [
+        (SPANISH, "hablar", "verbs"),
+        (SPANISH, "uno", "numbers"),
+        (FRENCH, "parler", "verbs"),
+    ];
+    for (deck_id, question, tag) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+        let uid = collection.decks[deck_id as usize].cards.last().expect("card added").uid;
+        collection
+            .set_tags(uid, vec![tag.to_string()])
+            .expect("tagging card");
+    }
+    collection
+}
+
+fn questions(collection: &Collection, deck_id: u32) -> Vec<&str> {
+    let pos = collection.deck_pos_by_id(deck_id).expect("deck is there");
+    collection.decks[pos].cards.iter().map(|card| card.question.as_str()).collect()
+}
+
+fn card_ids(collection: &Collection, deck_id: u32) -> Vec<u32> {
+    let pos = collection.deck_pos_by_id(deck_id).expect("deck is there");
+    collection.decks[pos].cards.iter().map(|card| card.id).collect()
+}
+
+#[test]
+fn matching_cards_are_borrowed_from_every_deck() {
+    let mut collection = sample();
+    let verbs = collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+
+    assert_eq!(questions(&collection, verbs), ["hablar", "parler"]);
+    assert_eq!(questions(&collection, SPANISH), ["uno"]);
+    assert!(questions(&collection, FRENCH).is_empty());
+    let borrowed = &collection.decks[2].cards[0];
+    assert_eq!(borrowed.home_deck(), Some(SPANISH));
+}
+
+#[test]
+fn emptying_returns_cards_under_their_old_ids() {
+    let mut collection = sample();
+    let verbs = collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+    collection.empty_filtered_deck(verbs).expect("emptying filtered deck");
+
+    assert!(questions(&collection, verbs).is_empty());
+    assert_eq!(questions(&collection, SPANISH), ["hablar", "uno"]);
+    assert_eq!(card_ids(&collection, SPANISH), [0, 1]);
+    assert_eq!(questions(&collection, FRENCH), ["parler"]);
+}
+
+#[test]
+fn rebuilding_borrows_what_matches_now() {
+    let mut collection = sample();
+    let verbs = collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+    collection
+        .add_card(SPANISH, "comer".to_string(), "-".to_string(), "".to_string())
+        .expect("adding card");
+    let uid = collection.decks[0].cards.last().expect("card added").uid;
+    collection
+        .set_tags(uid, vec!["verbs".to_string()])
+        .expect("tagging card");
+
+    collection.rebuild_filtered_deck(verbs).expect("rebuilding filtered deck");
+    assert_eq!(questions(&collection, verbs), ["hablar", "comer", "parler"]);
+    assert_eq!(questions(&collection, SPANISH), ["uno"]);
+}
+
+#[test]
+fn filtered_decks_only_borrow_from_regular_decks() {
+    let mut collection = sample();
+    collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+    let everything = collection
+        .add_filtered_deck("Everything", "", true)
+        .expect("adding filtered deck");
+
+    assert_eq!(questions(&collection, everything), ["uno"]);
+    assert!(collection
+        .add_card(everything, "dos".to_string(), "-".to_string(), "".to_string())
+        .is_err());
+}
+
+#[test]
+fn deleting_a_filtered_deck_returns_its_cards() {
+    let mut collection = sample();
+    let verbs = collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+    collection.trash_deck(verbs).expect("deleting filtered deck");
+
+    assert_eq!(questions(&collection, SPANISH), ["hablar", "uno"]);
+    assert_eq!(questions(&collection, FRENCH), ["parler"]);
+}
+
+#[test]
+fn deleting_a_home_deck_takes_its_lent_cards_with_it() {
+    let mut collection = sample();
+    let verbs = collection
+        .add_filtered_deck("Verbs", "tag:verbs", true)
+        .expect("adding filtered deck");
+    collection.trash_deck(SPANISH).expect("deleting deck");
+
+    assert_eq!(questions(&collection, verbs), ["parler"]);
+    let trashed = &collection.trash.decks[0].deck;
+    assert_eq!(trashed.cards.len(), 2);
+}
+
+#[test]
+fn bad_queries_are_refused() {
+    let mut collection = sample();
+    assert!(collection.add_filtered_deck("Broken", "is:sleeping", true).is_err());
+    assert_eq!(collection.decks.len(), 2);
+    assert!(collection.empty_filtered_deck(SPANISH).is_err());
+}
@@ -0,0 +1,98 @@
+//! Flagging cards with a colour and marking them, which quizzes show and
+//! searches can pick out.
+
+use uuid::Uuid;
+
+use ranki::collection::{Collection, Flag, Query};
+
+// A Spanish deck of three cards, none flagged or marked
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    for question in ["uno", "dos", "tres"] {
+        collection
+            .add_card(0, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+    }
+    collection
+}
+
+fn uid(collection: &Collection, question: &str) -> Uuid {
+    collection.decks[0]
+        .cards
+        .iter()
+        .find(|card| card.question == question)
+        .expect("card is there")
+        .uid
+}
+
+fn search<'a>(collection: &'a Collection, query: &str) -> Vec<&'a str> {
+    let query = Query::parse(query).expect("valid query");
+    collection
+        .search(&query)
+        .iter()
+        .map(|(_, card)| card.question.as_str())
+        .collect()
+}
+
+#[test]
+fn flags_are_searched_by_colour() {
+    let mut collection = sample();
+    collection
+        .set_flag(uid(&collection, "uno"), Some(Flag::Red))
+        .expect("flagging card");
+    collection
+        .set_flag(uid(&collection, "dos"), Some(Flag::Blue))
+        .expect("flagging card");
+
+    assert_eq!(search(&collection, "flag:red"), ["uno"]);
+    assert_eq!(search(&collection, "flag:Blue"), ["dos"]);
+    assert_eq!(search(&collection, "flag:any"), ["uno", "dos"]);
+    assert_eq!(search(&collection, "flag:none"), ["tres"]);
+}
+
+#[test]
+fn flags_can_be_cleared() {
+    let mut collection = sample();
+    let uno = uid(&collection, "uno");
+    collection.set_flag(uno, Some(Flag::Green)).expect("flagging card");
+    collection.set_flag(uno, None).expect("clearing flag");
+
+    assert!(search(&collection, "flag:any").is_empty());
+}
+
+#[test]
+fn marks_are_searchable_and_can_be_taken_off() {
+    let mut collection = sample();
+    let tres = uid(&collection, "tres");
+    collection.set_marked(tres, true).expect("marking card");
+    assert_eq!(search(&collection, "is:marked"), ["tres"]);
+    assert_eq!(search(&collection, "-is:marked"), ["uno", "dos"]);
+
+    collection.set_marked(tres, false).expect("unmarking card");
+    assert!(search(&collection, "is:marked").is_empty());
+}
+
+#[test]
+fn quizzes_show_flags_and_marks() {
+    let mut collection = sample();
+    collection
+        .set_flag(uid(&collection, "uno"), Some(Flag::Orange))
+        .expect("flagging card");
+    collection
+        .set_marked(uid(&collection, "dos"), true)
+        .expect("marking card");
+
+    let quiz = collection.get_quiz(None);
+    assert_eq!(quiz[0].flag, Some(Flag::Orange));
+    assert!(!quiz[0].marked);
+    assert_eq!(quiz[1].flag, None);
+    assert!(quiz[1].marked);
+}
+
+#[test]
+fn missing_cards_are_refused() {
+    let mut collection = sample();
+    assert!(collection.set_flag(Uuid::new_v4(), Some(Flag::Red)).is_err());
+    assert!(collection.set_marked(Uuid::new_v4(), true).is_err());
+}
@@ -0,0 +1,71 @@
+//! Notes kept alongside a card's question and answer, which are searchable and
+//! shown during quizzes.
+
+use ranki::collection::{Collection, Query};
+
+// A Spanish deck with one card noted as irregular and one without a note
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection
+        .add_card(0, "ir".to_string(), "to go".to_string(), "Irregular, see ser".to_string())
+        .expect("adding card");
+    collection
+        .add_card(0, "hablar".to_string(), "to speak".to_string(), "".to_string())
+        .expect("adding card");
+    collection
+}
+
+fn search<'a>(collection: &'a Collection, query: &str) -> Vec<&'a str> {
+    let query = Query::parse(query).expect("valid query");
+    collection
+        .search(&query)
+        .iter()
+        .map(|(_, card)| card.question.as_str())
+        .collect()
+}
+
+#[test]
+fn notes_are_searched_on_their_own_or_as_text() {
+    let collection = sample();
+    assert_eq!(search(&collection, "note:IRREGULAR"), ["ir"]);
+    assert_eq!(search(&collection, "ser"), ["ir"]);
+    assert!(search(&collection, "note:speak").is_empty());
+    assert_eq!(search(&collection, "-note:irregular"), ["hablar"]);
+}
+
+#[test]
+fn editing_a_card_changes_its_note() {
+    let mut collection = sample();
+    let uid = collection.decks[0].cards[1].uid;
+    collection
+        .edit_card(
+            uid,
+            "hablar".to_string(),
+            "to speak".to_string(),
+            "Regular -ar verb".to_string(),
+        )
+        .expect("editing card");
+
+    assert_eq!(collection.decks[0].cards[1].note, "Regular -ar verb");
+    assert_eq!(search(&collection, "note:regular"), ["ir", "hablar"]);
+}
+
+#[test]
+fn quizzes_show_the_note() {
+    let collection = sample();
+    let quiz = collection.get_quiz(None);
+    assert_eq!(quiz[0].note, "Irregular, see ser");
+    assert_eq!(quiz[1].note, "");
+}
+
+#[test]
+fn cards_are_found_by_the_uid_of_their_note() {
+    let collection = sample();
+    let card = &collection.decks[0].cards[0];
+    assert_ne!(card.note_uid, card.uid);
+
+    let (_, found) = collection.card_by_uid(card.note_uid).expect("card is there");
+    assert_eq!(found.question, "ir");
+    assert_eq!(search(&collection, &format!("id:{}", card.note_uid)), ["ir"]);
+}
@@ -0,0 +1,84 @@
+//! Moving collections kept outside of any profile, as older versions did, into
+//! the profile they would have been opened as.
+
+use std::fs;
+use std::path::Path;
+
+use ranki::collection::storage::{self, JsonStore};
+use ranki::collection::Profiles;
+
+mod common;
+
+use common::{save_collection, TempDir};
+
+fn deck_titles(dir: &Path) -> Vec<String> {
+    let collection = storage::open(Some("json"), dir, None)
+        .and_then(|mut storage| storage.load())
+        .expect("loading collection");
+    collection.decks.iter().map(|deck| deck.title.clone()).collect()
+}
+
+#[test]
+fn stray_collection_moves_into_last_profile_once_profiles_exist() {
+    let data = TempDir::new("stray-after-profiles");
+    let stray = TempDir::new("stray-after-profiles-cwd");
+    let mut profiles = Profiles::open(&data).expect("opening profiles");
+    let work = profiles.create("Work").expect("creating profile");
+    profiles.set_last(work).expect("setting last profile");
+
+    save_collection(&stray.join(JsonStore::FILE), "Stray");
+    let profiles = Profiles::open(&data).expect("reopening profiles");
+    profiles.adopt(&stray).expect("adopting stray collection");
+
+    assert!(!stray.join(JsonStore::FILE).exists());
+    assert!(!data.join(JsonStore::FILE).exists());
+    assert_eq!(deck_titles(&profiles.dir_of(work)), ["Stray"]);
+}
+
+#[test]
+fn collection_at_data_root_is_taken_over_on_every_open() {
+    let data = TempDir::new("root-after-profiles");
+    let profiles = Profiles::open(&data).expect("opening profiles");
+    let default = profiles.last().expect("default profile");
+
+    save_collection(&data.join(JsonStore::FILE), "Root");
+    let profiles = Profiles::open(&data).expect("reopening profiles");
+
+    assert!(!data.join(JsonStore::FILE).exists());
+    assert_eq!(deck_titles(&profiles.dir_of(default)), ["Root"]);
+}
+
+#[test]
+fn stray_collection_is_left_alone_if_profile_has_one() {
+    let data = TempDir::new("stray-kept");
+    let stray = TempDir::new("stray-kept-cwd");
+    let profiles = Profiles::open(&data).expect("opening profiles");
+    let default = profiles.last().expect("default profile");
+    save_collection(&profiles.dir_of(default).join(JsonStore::FILE), "Existing");
+
+    save_collection(&stray.join(JsonStore::FILE), "Stray");
+    profiles.adopt(&stray).expect("adopting stray collection");
+
+    assert!(stray.join(JsonStore::FILE).exists());
+    assert_eq!(deck_titles(&profiles.dir_of(default)), ["Existing"]);
+}
+
+#[test]
+fn only_backups_are_taken_from_a_stray_backups_directory() {
+    let data = TempDir::new("stray-backups");
+    let stray = TempDir::new("stray-backups-cwd");
+    let profiles = Profiles::open(&data).expect("opening profiles");
+    let default = profiles.last().expect("default profile");
+
+    save_collection(&stray.join(JsonStore::FILE), "Stray");
+    let backup = "backup-1600000000.000.json";
+    save_collection(&stray.join("backups").join(backup), "Backed Up");
+    fs::write(stray.join("backups").join("notes.txt"), "not a backup").expect("writing file");
+    profiles.adopt(&stray).expect("adopting stray collection");
+
+    let moved = profiles.dir_of(default).join("backups");
+    assert!(moved.join(backup).exists());
+    assert!(!moved.join("notes.txt").exists());
+    assert!(stray.join("backups").join("notes.txt").exists());
+    assert!(!stray.join("backups").join(backup).exists());
+}
@@ -0,0 +1,71 @@
+//! Starting from a collection file that can't be read.
+
+use std::fs;
+use std::path::Path;
+
+use ranki::collection::storage::{JsonStore, Storage};
+
+mod common;
+
+use common::{save_collection, TempDir};
+
+fn corrupt_files(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .expect("listing directory")
+        .filter(|entry| {
+            let name = entry.as_ref().expect("reading entry").file_name();
+            name.to_string_lossy().contains(".corrupt-")
+        })
+        .count()
+}
+
+#[test]
+fn unreadable_collection_is_restored_from_newest_backup() {
+    let dir = TempDir::new("recover-backup");
+    let backups = dir.join("backups");
+    fs::create_dir_all(&backups).expect("creating backups directory");
+    save_collection(&backups.join("backup-1000.json"), "Older");
+    save_collection(&backups.join("backup-2000.json"), "Newer");
+    fs::write(dir.join(JsonStore::FILE), "{ not json").expect("corrupting collection");
+
+    let collection = JsonStore::new(dir.join(JsonStore::FILE))
+        .load()
+        .expect("loading collection");
+
+    assert_eq!(collection.decks[0].title, "Newer");
+    assert_eq!(corrupt_files(&dir), 1);
+}
+
+#[test]
+fn unreadable_backups_are_skipped() {
+    let dir = TempDir::new("recover-skip");
+    let backups = dir.join("backups");
+    fs::create_dir_all(&backups).expect("creating backups directory");
+    save_collection(&backups.join("backup-1000.json"), "Older");
+    fs::write(backups.join("backup-2000.json"), "{ not json").expect("corrupting backup");
+    fs::write(dir.join(JsonStore::FILE), "{ not json").expect("corrupting collection");
+
+    let collection = JsonStore::new(dir.join(JsonStore::FILE))
+        .load()
+        .expect("loading collection");
+
+    assert_eq!(collection.decks[0].title, "Older");
+}
+
+#[test]
+fn unreadable_collection_without_backup_is_never_started_afresh() {
+    let dir = TempDir::new("recover-refuse");
+    fs::write(dir.join(JsonStore::FILE), "{ not json").expect("corrupting collection");
+
+    assert!(JsonStore::new(dir.join(JsonStore::FILE)).load().is_err());
+    assert_eq!(corrupt_files(&dir), 1);
+    assert!(JsonStore::new(dir.join(JsonStore::FILE)).load().is_err());
+
+    for entry in fs::read_dir(&dir).expect("listing directory") {
+        fs::remove_file(entry.expect("reading entry").path()).expect("deleting set aside file");
+    }
+    let collection = JsonStore::new(dir.join(JsonStore::FILE))
+        .load()
+        .expect("starting a new collection");
+    assert!(collection.decks.is_empty());
+}
@@ -0,0 +1,104 @@
+//! Find-and-replace over the cards matching a search, previewed before it's
+//! applied.
+
+use ranki::collection::{Collection, Replace};
+
+// A Spanish deck and a French one, both with cards about colours
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    let cards = [
+        (0, "colour: red", "rojo", "colour words agree"),
+        (0, "colour: green", "verde", ""),
+        (1, "colour: red", "rouge", ""),
+    ];
+    for (deck_id, question, answer, note) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), answer.to_string(), note.to_string())
+            .expect("adding card");
+    }
+    collection
+}
+
+fn replace(find: &str, replacement: &str, query: &str) -> Replace {
+    Replace {
+        find: find.to_string(),
+        replacement: replacement.to_string(),
+        regex: false,
+        query: query.to_string(),
+        questions: true,
+        answers: true,
+        notes: true,
+    }
+}
+
+fn questions(collection: &Collection) -> Vec<&str> {
+    collection
+        .decks
+        .iter()
+        .flat_map(|deck| deck.cards.iter().map(|card| card.question.as_str()))
+        .collect()
+}
+
+#[test]
+fn replacing_is_scoped_to_the_search() {
+    let mut collection = sample();
+    let changed = collection
+        .find_replace(&replace("colour", "color", "deck:Spanish"))
+        .expect("replacing");
+
+    assert_eq!(changed, 2);
+    assert_eq!(questions(&collection), ["color: red", "color: green", "colour: red"]);
+    assert_eq!(collection.decks[0].cards[0].note, "color words agree");
+}
+
+#[test]
+fn only_the_chosen_fields_change() {
+    let mut collection = sample();
+    let mut notes_only = replace("colour", "color", "");
+    notes_only.questions = false;
+    notes_only.answers = false;
+
+    assert_eq!(collection.find_replace(&notes_only).expect("replacing"), 1);
+    assert_eq!(questions(&collection), ["colour: red", "colour: green", "colour: red"]);
+    assert_eq!(collection.decks[0].cards[0].note, "color words agree");
+}
+
+#[test]
+fn regular_expressions_can_use_capture_groups() {
+    let mut collection = sample();
+    let mut swap = replace(r"colour: (\w+)", "$1 colour", "");
+    swap.regex = true;
+
+    collection.find_replace(&swap).expect("replacing");
+    assert_eq!(questions(&collection), ["red colour", "green colour", "red colour"]);
+}
+
+#[test]
+fn previewing_changes_nothing() {
+    let collection = sample();
+    let preview = collection
+        .preview_replace(&replace("red", "blue", ""))
+        .expect("previewing");
+
+    let changed: Vec<_> = preview.iter().map(|change| change.uid).collect();
+    assert_eq!(
+        changed,
+        [collection.decks[0].cards[0].uid, collection.decks[1].cards[0].uid]
+    );
+    assert_eq!(preview[0].after.0, "colour: blue");
+    assert_eq!(questions(&collection), ["colour: red", "colour: green", "colour: red"]);
+}
+
+#[test]
+fn bad_requests_change_nothing() {
+    let mut collection = sample();
+    let mut invalid = replace("colour: (", "", "");
+    invalid.regex = true;
+
+    assert!(collection.find_replace(&invalid).is_err());
+    assert!(collection.find_replace(&replace("", "x", "")).is_err());
+    assert!(collection.find_replace(&replace("colour", "color", "flag:")).is_err());
+    assert_eq!(questions(&collection), ["colour: red", "colour: green", "colour: red"]);
+}
@@ -0,0 +1,88 @@
+//! Keeping what a card said before each edit, and reverting to it.
+
+use uuid::Uuid;
+
+use ranki::collection::{Card, Collection};
+
+// A Spanish deck with a single card
+fn sample() -> (Collection, Uuid) {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection
+        .add_card(0, "uno".to_string(), "one".to_string(), "".to_string())
+        .expect("adding card");
+    let uid = collection.decks[0].cards[0].uid;
+    (collection, uid)
+}
+
+fn edit(collection: &mut Collection, uid: Uuid, question: &str, answer: &str) {
+    collection
+        .edit_card(uid, question.to_string(), answer.to_string(), "".to_string())
+        .expect("editing card");
+}
+
+fn card(collection: &Collection, uid: Uuid) -> &Card {
+    collection.card_by_uid(uid).expect("card is there").1
+}
+
+#[test]
+fn edits_keep_the_old_content() {
+    let (mut collection, uid) = sample();
+    edit(&mut collection, uid, "uno", "1");
+    edit(&mut collection, uid, "una", "1");
+
+    let card = card(&collection, uid);
+    assert_eq!((card.question.as_str(), card.answer.as_str()), ("una", "1"));
+    let old: Vec<_> = card
+        .revisions()
+        .iter()
+        .map(|revision| (revision.question.as_str(), revision.answer.as_str()))
+        .collect();
+    assert_eq!(old, [("uno", "one"), ("uno", "1")]);
+}
+
+#[test]
+fn unchanged_edits_are_not_kept() {
+    let (mut collection, uid) = sample();
+    let modified = card(&collection, uid).modified();
+    edit(&mut collection, uid, "uno", "one");
+
+    assert!(card(&collection, uid).revisions().is_empty());
+    assert_eq!(card(&collection, uid).modified(), modified);
+}
+
+#[test]
+fn edits_bump_the_modified_time() {
+    let (mut collection, uid) = sample();
+    let created = card(&collection, uid).created().expect("added with a time");
+    edit(&mut collection, uid, "uno", "1");
+
+    let card = card(&collection, uid);
+    let modified = card.modified().expect("edited with a time");
+    assert_eq!(card.created(), Some(created));
+    assert!(modified >= created);
+    assert_eq!(card.revisions()[0].replaced, modified);
+}
+
+#[test]
+fn reverting_brings_back_old_content_as_a_new_edit() {
+    let (mut collection, uid) = sample();
+    edit(&mut collection, uid, "uno", "1");
+    edit(&mut collection, uid, "una", "1");
+    collection.revert_card(uid, 0).expect("reverting card");
+
+    let card = card(&collection, uid);
+    assert_eq!((card.question.as_str(), card.answer.as_str()), ("uno", "one"));
+    assert_eq!(card.revisions().len(), 3);
+    assert_eq!(card.revisions()[2].question, "una");
+}
+
+#[test]
+fn reverting_to_a_missing_revision_is_refused() {
+    let (mut collection, uid) = sample();
+    edit(&mut collection, uid, "uno", "1");
+
+    assert!(collection.revert_card(uid, 1).is_err());
+    assert!(collection.revert_card(Uuid::new_v4(), 0).is_err());
+    assert_eq!(card(&collection, uid).answer, "1");
+}
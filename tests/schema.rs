@@ -0,0 +1,146 @@
+//! Loads a collection saved by each earlier version of Ranki, to check they
+//! all still upgrade to the current format. The fixtures were written by the
+//! code of the time from the same sample collection, so each has the fields
+//! its version knew about.
+
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use ranki::collection::{schema, Collection, Flag};
+
+const UNVERSIONED: [(&str, &str); 8] = [
+    ("original", include_str!("fixtures/v0-original.json")),
+    ("tags", include_str!("fixtures/v0-tags.json")),
+    ("filtered-decks", include_str!("fixtures/v0-filtered-decks.json")),
+    ("flags", include_str!("fixtures/v0-flags.json")),
+    ("trash", include_str!("fixtures/v0-trash.json")),
+    ("revisions", include_str!("fixtures/v0-revisions.json")),
+    ("uids", include_str!("fixtures/v0-uids.json")),
+    ("backup-settings", include_str!("fixtures/v0-backup-settings.json")),
+];
+
+// Saved just before notes were given uids of their own
+const NOTE_UIDS: &str = include_str!("fixtures/v1-note-uids.json");
+
+fn load(name: &str, json: &str) -> Collection {
+    let value: Value = serde_json::from_str(json).expect("fixture is valid JSON");
+    match schema::load(value) {
+        Ok(collection) => collection,
+        Err(e) => panic!("loading {}: {}", name, e),
+    }
+}
+
+fn saved_version(collection: &Collection) -> Value {
+    serde_json::to_value(collection).expect("serialising")["version"].clone()
+}
+
+#[test]
+fn every_unversioned_format_loads() {
+    for (name, json) in UNVERSIONED.iter() {
+        let c = load(name, json);
+        assert_eq!(saved_version(&c), json!(schema::VERSION), "{}", name);
+
+        let titles: Vec<&str> = c.decks.iter().map(|deck| deck.title.as_str()).collect();
+        assert_eq!(titles, ["Spanish", "Verbs"], "{}", name);
+        let spanish = &c.decks[0];
+        assert_eq!(spanish.cards.len(), 2, "{}", name);
+        assert_eq!(spanish.cards[1].question, "comer", "{}", name);
+        assert_eq!(spanish.cards[1].note, "regular -er verb", "{}", name);
+        let uid = spanish.cards[1].uid;
+        assert!(c.card_by_uid(uid).is_some(), "{} is indexed", name);
+    }
+}
+
+#[test]
+fn fields_are_kept_from_the_version_that_added_them() {
+    for (name, json) in UNVERSIONED.iter().skip(1) {
+        let card = &load(name, json).decks[0].cards[1];
+        assert_eq!(card.tags, ["verbs"], "{}", name);
+    }
+    for (name, json) in UNVERSIONED.iter().skip(2) {
+        let c = load(name, json);
+        let filter = c.decks[1].filter.as_ref().expect("filtered deck");
+        assert_eq!(filter.query, "tag:verbs", "{}", name);
+    }
+    for (name, json) in UNVERSIONED.iter().skip(3) {
+        let card = &load(name, json).decks[0].cards[1];
+        assert_eq!(card.flag, Some(Flag::Red), "{}", name);
+        assert!(card.marked, "{}", name);
+    }
+    for (name, json) in UNVERSIONED.iter().skip(4) {
+        let c = load(name, json);
+        assert_eq!(c.trash.decks.len(), 1, "{}", name);
+        assert_eq!(c.trash.cards.len(), 1, "{}", name);
+        assert_eq!(c.trash.decks[0].deck.title, "French", "{}", name);
+    }
+}
+
+#[test]
+fn missing_fields_are_filled_in() {
+    let (name, json) = UNVERSIONED[0];
+    let c = load(name, json);
+    let card = &c.decks[0].cards[1];
+    assert!(card.tags.is_empty());
+    assert_eq!(card.flag, None);
+    assert!(!card.marked);
+    assert!(card.revisions().is_empty());
+    assert!(c.decks[1].filter.is_none());
+    assert!(c.trash.decks.is_empty());
+}
+
+#[test]
+fn generated_uids_are_unique() {
+    for (name, json) in UNVERSIONED.iter() {
+        let c = load(name, json);
+        let mut uids = HashSet::new();
+        let trashed = c.trash.decks.iter().map(|t| &t.deck);
+        for deck in c.decks.iter().map(|deck| &**deck).chain(trashed) {
+            assert!(uids.insert(deck.uid), "{} deck uid repeated", name);
+            for card in deck.cards.iter() {
+                assert!(uids.insert(card.uid), "{} card uid repeated", name);
+                assert!(uids.insert(card.note_uid), "{} note uid repeated", name);
+            }
+        }
+        for trashed in c.trash.cards.iter() {
+            assert!(uids.insert(trashed.card.uid), "{} card uid repeated", name);
+            assert!(uids.insert(trashed.card.note_uid), "{} note uid repeated", name);
+        }
+    }
+}
+
+#[test]
+fn existing_uids_are_kept() {
+    let (name, json) = UNVERSIONED[6];
+    let value: Value = serde_json::from_str(json).expect("fixture is valid JSON");
+    let c = load(name, json);
+    assert_eq!(json!(c.decks[0].uid), value["decks"][0]["uid"]);
+    assert_eq!(json!(c.decks[0].cards[1].uid), value["decks"][0]["cards"][1]["uid"]);
+}
+
+#[test]
+fn notes_are_given_uids() {
+    let value: Value = serde_json::from_str(NOTE_UIDS).expect("fixture is valid JSON");
+    let c = load("note-uids", NOTE_UIDS);
+    assert_eq!(saved_version(&c), json!(schema::VERSION));
+
+    let card = &c.decks[0].cards[1];
+    assert_eq!(json!(card.uid), value["decks"][0]["cards"][1]["uid"]);
+    assert_ne!(card.note_uid, card.uid);
+    assert_eq!(c.card_by_uid(card.note_uid).map(|(_, found)| found.uid), Some(card.uid));
+}
+
+#[test]
+fn current_format_round_trips() {
+    let (name, json) = UNVERSIONED[UNVERSIONED.len() - 1];
+    let saved = serde_json::to_value(load(name, json)).expect("serialising");
+    let reloaded = schema::load(saved.clone()).expect("loading current format");
+    assert_eq!(serde_json::to_value(reloaded).expect("serialising"), saved);
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let mut value = serde_json::to_value(Collection::new()).expect("serialising");
+    value["version"] = json!(schema::VERSION + 1);
+    assert!(schema::load(value).is_err());
+}
@@ -0,0 +1,73 @@
+//! Quizzing a deck along with its children, which are the decks named after it
+//! with `::`.
+
+use ranki::collection::Collection;
+
+// Languages with Spanish and Spanish::Verbs nested under it, along with a
+// deck whose title merely starts the same way and one on its own
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    let decks = [
+        ("Languages", "hola"),
+        ("Languages::Spanish", "uno"),
+        ("Languages::Spanish::Verbs", "hablar"),
+        ("Languages Extra", "hello"),
+        ("Maths", "1 + 1"),
+    ];
+    for (deck_id, (title, question)) in decks.iter().enumerate() {
+        collection.add_deck(title);
+        collection
+            .add_card(deck_id as u32, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+    }
+    collection
+}
+
+fn quiz(collection: &Collection, deck_ids: Option<&[u32]>) -> Vec<String> {
+    let mut questions: Vec<_> = collection
+        .get_quiz(deck_ids)
+        .into_iter()
+        .map(|card| card.question)
+        .collect();
+    questions.sort();
+    questions
+}
+
+#[test]
+fn children_are_nested_by_title() {
+    let collection = sample();
+    assert_eq!(collection.with_children(&[0]), [0, 1, 2]);
+    assert_eq!(collection.with_children(&[1]), [1, 2]);
+    assert_eq!(collection.with_children(&[2]), [2]);
+    assert_eq!(collection.with_children(&[2, 4]), [2, 4]);
+    assert!(collection.with_children(&[9]).is_empty());
+}
+
+#[test]
+fn quizzing_a_deck_takes_in_its_children() {
+    let collection = sample();
+    assert_eq!(quiz(&collection, Some(&[0])), ["hablar", "hola", "uno"]);
+    assert_eq!(quiz(&collection, Some(&[1])), ["hablar", "uno"]);
+    assert_eq!(quiz(&collection, Some(&[1, 4])), ["1 + 1", "hablar", "uno"]);
+}
+
+#[test]
+fn quizzing_without_a_scope_takes_in_everything() {
+    let collection = sample();
+    assert_eq!(quiz(&collection, None).len(), 5);
+    assert!(quiz(&collection, Some(&[])).is_empty());
+}
+
+#[test]
+fn due_counts_take_in_children() {
+    let collection = sample();
+    assert_eq!(collection.due_count(0), 3);
+    assert_eq!(collection.due_count(1), 2);
+    assert_eq!(collection.due_count(3), 1);
+}
+
+#[test]
+fn overlapping_scopes_ask_each_card_once() {
+    let collection = sample();
+    assert_eq!(quiz(&collection, Some(&[0, 1, 2])), ["hablar", "hola", "uno"]);
+}
@@ -0,0 +1,93 @@
+//! Parsing search queries and matching them against the cards of a
+//! collection, as the card browser does.
+
+use ranki::collection::{Collection, Query};
+
+// A Spanish deck of verbs and numbers, and a French deck with one verb
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French Verbs");
+    let cards = [
+        (0, "hablar", "to speak", "verbs"),
+        (0, "comer", "to eat", "verbs"),
+        (0, "uno", "one", "numbers"),
+        (1, "parler", "to speak", "verbs"),
+    ];
+    for (deck_id, question, answer, tag) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), answer.to_string(), "".to_string())
+            .expect("adding card");
+        let uid = collection.decks[deck_id as usize].cards.last().expect("card added").uid;
+        collection
+            .set_tags(uid, vec![tag.to_string()])
+            .expect("tagging card");
+    }
+    collection
+}
+
+fn search<'a>(collection: &'a Collection, query: &str) -> Vec<&'a str> {
+    let query = Query::parse(query).expect("valid query");
+    collection
+        .search(&query)
+        .iter()
+        .map(|(_, card)| card.question.as_str())
+        .collect()
+}
+
+#[test]
+fn empty_query_matches_everything() {
+    let collection = sample();
+    assert!(Query::parse("  ").expect("valid query").is_empty());
+    assert_eq!(search(&collection, ""), ["hablar", "comer", "uno", "parler"]);
+}
+
+#[test]
+fn every_term_has_to_match() {
+    let collection = sample();
+    assert_eq!(search(&collection, "TAG:Verbs deck:spanish"), ["hablar", "comer"]);
+    assert_eq!(search(&collection, "speak tag:verbs"), ["hablar", "parler"]);
+}
+
+#[test]
+fn terms_can_be_negated() {
+    let collection = sample();
+    assert_eq!(search(&collection, "-tag:verbs"), ["uno"]);
+    assert_eq!(search(&collection, "to -speak"), ["comer"]);
+}
+
+#[test]
+fn quotes_keep_spaces_in_a_term() {
+    let collection = sample();
+    assert_eq!(search(&collection, "\"deck:French Verbs\""), ["parler"]);
+    assert_eq!(search(&collection, "\"to eat\""), ["comer"]);
+    assert!(search(&collection, "deck:French Verbs").is_empty());
+}
+
+#[test]
+fn unknown_keys_are_searched_as_text() {
+    let mut collection = sample();
+    collection
+        .add_card(0, "time: 5pm".to_string(), "las cinco".to_string(), "".to_string())
+        .expect("adding card");
+    assert_eq!(search(&collection, "time:"), ["time: 5pm"]);
+}
+
+#[test]
+fn card_states_are_searchable() {
+    let mut collection = sample();
+    let uid = collection.decks[0].cards[0].uid;
+    collection
+        .process_results(vec![(uid, Some(true))])
+        .expect("answering card");
+
+    assert_eq!(search(&collection, "is:new"), ["comer", "uno", "parler"]);
+    assert_eq!(search(&collection, "-is:new -is:due"), ["hablar"]);
+}
+
+#[test]
+fn bad_terms_are_refused() {
+    for query in ["is:sleeping", "flag:mauve", "failed:soon", "id:not-a-uid"] {
+        assert!(Query::parse(query).is_err(), "{}", query);
+    }
+}
@@ -0,0 +1,275 @@
+//! Driving the collection service the way the window does, over a collection
+//! kept in memory, and reading back what it sends.
+
+use std::fs;
+use std::sync::mpsc;
+
+use uuid::Uuid;
+
+use ranki::collection::storage::JsonStore;
+use ranki::collection::{Action, Collection, CollectionService, Profiles, SendData};
+
+mod common;
+
+use common::TempDir;
+
+/// A service over a collection in memory, with what it sends back
+struct Service {
+    /// Data directory, which profiles and backups are kept in
+    data: TempDir,
+    service: CollectionService,
+    updates: mpsc::Receiver<SendData>,
+}
+
+impl Service {
+    fn new(name: &str) -> Self {
+        Service::with_storage(name, "memory")
+    }
+
+    fn with_storage(name: &str, kind: &str) -> Self {
+        let data = TempDir::new(name);
+        let profiles = Profiles::open(&data).expect("opening profiles");
+        let profile = profiles.last().expect("default profile");
+        let (tx, updates) = mpsc::channel();
+        let service =
+            CollectionService::new(tx, Some(kind.to_string()), None, profiles, profile)
+                .expect("opening collection");
+        Service {
+            data,
+            service,
+            updates,
+        }
+    }
+
+    // Applies the actions as one batch, as if sent together by the window,
+    // returning everything sent back
+    fn send(&mut self, actions: Vec<Action>) -> Vec<SendData> {
+        let (tx, rx) = mpsc::channel();
+        for action in actions.into_iter().chain(Some(Action::Quit)) {
+            tx.send(action).expect("sending action");
+        }
+        self.service.listen(rx);
+        self.updates.try_iter().collect()
+    }
+}
+
+// The collection the window would be showing after what was sent
+fn collection(sent: &[SendData]) -> &Collection {
+    sent.iter()
+        .rev()
+        .find_map(|data| match data {
+            SendData::Collection(collection) => Some(collection),
+            _ => None,
+        })
+        .expect("collection sent")
+}
+
+// What the undo and redo buttons would say after what was sent
+fn history(sent: &[SendData]) -> (Option<&str>, Option<&str>) {
+    sent.iter()
+        .rev()
+        .find_map(|data| match data {
+            SendData::History(undo, redo) => Some((undo.as_deref(), redo.as_deref())),
+            _ => None,
+        })
+        .expect("history sent")
+}
+
+fn deck_titles(collection: &Collection) -> Vec<&str> {
+    collection.decks.iter().map(|deck| deck.title.as_str()).collect()
+}
+
+#[test]
+fn changes_are_saved_and_reloaded() {
+    let mut service = Service::new("service-save");
+    let sent = service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddCard(0, "uno".to_string(), "one".to_string(), "".to_string()),
+    ]);
+    assert_eq!(deck_titles(collection(&sent)), ["Spanish"]);
+
+    let sent = service.send(vec![Action::ReloadCollection]);
+    let reloaded = collection(&sent);
+    assert_eq!(deck_titles(reloaded), ["Spanish"]);
+    assert_eq!(reloaded.decks[0].cards[0].question, "uno");
+}
+
+#[test]
+fn failed_actions_change_nothing() {
+    let mut service = Service::new("service-failed");
+    service.send(vec![Action::AddDeck("Spanish".to_string())]);
+    let sent = service.send(vec![
+        Action::DeleteDeck(7),
+        Action::AddCard(7, "uno".to_string(), "one".to_string(), "".to_string()),
+    ]);
+    assert!(sent.is_empty());
+
+    let sent = service.send(vec![Action::Undo]);
+    assert_eq!(history(&sent), (None, Some("Add Deck")));
+}
+
+#[test]
+fn undo_and_redo_step_through_changes() {
+    let mut service = Service::new("service-undo");
+    let sent = service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddDeck("French".to_string()),
+        Action::EditDeck(1, "German".to_string()),
+        Action::Undo,
+    ]);
+    assert_eq!(deck_titles(collection(&sent)), ["Spanish", "French"]);
+    assert_eq!(history(&sent), (Some("Add Deck"), Some("Rename Deck")));
+
+    let sent = service.send(vec![Action::Undo, Action::Undo]);
+    assert!(collection(&sent).decks.is_empty());
+    assert_eq!(history(&sent), (None, Some("Add Deck")));
+
+    let sent = service.send(vec![Action::Redo, Action::Redo, Action::Redo]);
+    assert_eq!(deck_titles(collection(&sent)), ["Spanish", "German"]);
+    assert_eq!(history(&sent), (Some("Rename Deck"), None));
+}
+
+#[test]
+fn new_changes_clear_what_could_be_redone() {
+    let mut service = Service::new("service-redo-cleared");
+    service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddDeck("French".to_string()),
+        Action::Undo,
+    ]);
+    let sent = service.send(vec![Action::AddDeck("German".to_string())]);
+    assert_eq!(deck_titles(collection(&sent)), ["Spanish", "German"]);
+    assert_eq!(history(&sent), (Some("Add Deck"), None));
+}
+
+#[test]
+fn undone_changes_are_saved_too() {
+    let mut service = Service::new("service-undo-saved");
+    service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddDeck("French".to_string()),
+    ]);
+    service.send(vec![Action::Undo]);
+
+    let sent = service.send(vec![Action::ReloadCollection]);
+    assert_eq!(deck_titles(collection(&sent)), ["Spanish"]);
+    let sent = service.send(vec![Action::Undo]);
+    assert_eq!(history(&sent), (Some("Add Deck"), Some("Reload Collection")));
+}
+
+#[test]
+fn restored_backups_stay_in_the_open_profile() {
+    let mut service = Service::new("service-restore-profile");
+    let sent = service.send(vec![Action::AddDeck("Spanish".to_string()), Action::BackUp]);
+    let backup = sent
+        .iter()
+        .find_map(|data| match data {
+            SendData::Backups(backups) => backups.first().map(|backup| backup.name.clone()),
+            _ => None,
+        })
+        .expect("backup taken");
+
+    // A backup of the first profile, copied over to a second one
+    service.send(vec![
+        Action::CreateProfile("Work".to_string()),
+        Action::SwitchProfile(1, None),
+    ]);
+    let profiles = Profiles::open(&service.data).expect("opening profiles");
+    let backups = profiles.dir_of(1).join("backups");
+    fs::create_dir_all(&backups).expect("creating backups directory");
+    fs::copy(profiles.dir_of(0).join("backups").join(&backup), backups.join(&backup))
+        .expect("copying backup");
+
+    let sent = service.send(vec![Action::RestoreBackup(backup), Action::DeleteProfile(1)]);
+    let restored = collection(&sent);
+    assert_eq!(deck_titles(restored), ["Spanish"]);
+    assert_eq!((restored.id, restored.owner.as_str()), (1, "Work"));
+    let sent = service.send(vec![Action::ListProfiles]);
+    let names: Vec<&str> = sent
+        .iter()
+        .find_map(|data| match data {
+            SendData::Profiles(profiles) => Some(profiles),
+            _ => None,
+        })
+        .expect("profiles sent")
+        .iter()
+        .map(|profile| profile.name.as_str())
+        .collect();
+    assert_eq!(names, ["Default", "Work"]);
+}
+
+#[test]
+fn undo_keeps_the_profile_name() {
+    let mut service = Service::new("service-undo-rename");
+    service.send(vec![Action::AddDeck("Spanish".to_string())]);
+    service.send(vec![Action::RenameProfile(0, "Languages".to_string())]);
+
+    let sent = service.send(vec![Action::Undo]);
+    let undone = collection(&sent);
+    assert!(undone.decks.is_empty());
+    assert_eq!(undone.owner, "Languages");
+    let sent = service.send(vec![Action::Redo]);
+    assert_eq!(collection(&sent).owner, "Languages");
+}
+
+#[test]
+fn nothing_is_saved_over_an_unanswered_conflict() {
+    let mut service = Service::with_storage("service-conflict", "json");
+    service.send(vec![Action::AddDeck("Spanish".to_string())]);
+    let path = Profiles::open(&service.data)
+        .expect("opening profiles")
+        .dir_of(0)
+        .join(JsonStore::FILE);
+
+    // Something else starts writing the file, so the next save is refused
+    fs::write(&path, "{").expect("writing part of the file");
+    service.send(vec![Action::AddDeck("French".to_string())]);
+    let mut outside = Collection::new();
+    outside.add_deck("German");
+    fs::write(&path, serde_json::to_vec(&outside).expect("serialising"))
+        .expect("finishing the file");
+
+    let sent = service.send(vec![Action::AddDeck("Italian".to_string())]);
+    assert!(sent.iter().any(|data| matches!(data, SendData::Conflict)));
+    let sent = service.send(vec![Action::ReloadCollection]);
+    assert_eq!(deck_titles(collection(&sent)), ["German"]);
+}
+
+#[test]
+fn quiz_answers_are_undone_together() {
+    let mut service = Service::new("service-quiz-undo");
+    let sent = service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddCard(0, "uno".to_string(), "one".to_string(), "".to_string()),
+        Action::AddCard(0, "dos".to_string(), "two".to_string(), "".to_string()),
+    ]);
+    let uids: Vec<Uuid> = collection(&sent).decks[0].cards.iter().map(|card| card.uid).collect();
+
+    let sent = service.send(vec![Action::QuizResults(vec![
+        (uids[0], Some(true)),
+        (uids[1], Some(false)),
+    ])]);
+    assert_eq!(collection(&sent).due_count(0), 1);
+    assert_eq!(history(&sent), (Some("Quiz Answers"), None));
+
+    let sent = service.send(vec![Action::Undo]);
+    assert_eq!(collection(&sent).due_count(0), 2);
+}
+
+#[test]
+fn quiz_answers_for_a_missing_card_change_nothing() {
+    let mut service = Service::new("service-quiz-missing");
+    let sent = service.send(vec![
+        Action::AddDeck("Spanish".to_string()),
+        Action::AddCard(0, "uno".to_string(), "one".to_string(), "".to_string()),
+    ]);
+    let uid = collection(&sent).decks[0].cards[0].uid;
+
+    let sent = service.send(vec![Action::QuizResults(vec![
+        (uid, Some(true)),
+        (Uuid::new_v4(), Some(true)),
+    ])]);
+    assert!(sent.is_empty());
+    let sent = service.send(vec![Action::Undo]);
+    assert_eq!(history(&sent), (Some("Add Deck"), Some("Add Card")));
+}
@@ -0,0 +1,83 @@
+//! Saving collections to the SQLite database, which keeps a row per deck and
+//! per card keyed by uid.
+
+use std::path::Path;
+
+use ranki::collection::storage::{self, JsonStore, Storage};
+use ranki::collection::{schema, Collection};
+
+mod common;
+
+use common::TempDir;
+
+fn open(dir: &Path) -> Box<dyn Storage> {
+    storage::open(Some("sqlite"), dir, None).expect("opening database")
+}
+
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    for (deck_id, question) in [(0, "uno"), (0, "dos"), (1, "un"), (1, "deux")] {
+        collection
+            .add_card(deck_id, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+    }
+    collection
+}
+
+// The sample with the second Spanish card given the uid of the first
+fn sample_sharing_uid() -> Collection {
+    let mut value = serde_json::to_value(sample()).expect("saving collection");
+    let uid = value["decks"][0]["cards"][0]["uid"].clone();
+    value["decks"][0]["cards"][1]["uid"] = uid;
+    schema::load(value).expect("loading collection")
+}
+
+fn questions(collection: &Collection) -> Vec<&str> {
+    let cards = collection.decks.iter().flat_map(|deck| deck.cards.iter());
+    cards.map(|card| card.question.as_str()).collect()
+}
+
+#[test]
+fn shared_uids_are_given_new_ones_when_copying_from_json() {
+    let dir = TempDir::new("sqlite-copy");
+    JsonStore::new(dir.join(JsonStore::FILE))
+        .save(&sample_sharing_uid())
+        .expect("saving collection");
+
+    let collection = open(&dir).load().expect("loading collection");
+    assert_eq!(questions(&collection), ["uno", "dos", "un", "deux"]);
+}
+
+#[test]
+fn collection_with_shared_uids_is_refused() {
+    let dir = TempDir::new("sqlite-refuse");
+    let mut store = open(&dir);
+    store.load().expect("loading empty database");
+
+    assert!(store.save(&sample_sharing_uid()).is_err());
+    assert!(open(&dir).load().expect("loading collection").decks.is_empty());
+}
+
+#[test]
+fn changes_are_saved_and_loaded_back() {
+    let dir = TempDir::new("sqlite-changes");
+    let mut store = open(&dir);
+    store.load().expect("loading empty database");
+    let mut collection = sample();
+    store.save(&collection).expect("saving collection");
+
+    let un = collection.decks[1].cards[0].uid;
+    collection
+        .edit_card(un, "une".to_string(), "-".to_string(), "".to_string())
+        .expect("editing card");
+    collection.remove_deck_by_id(0);
+    collection.add_deck("German");
+    store.save(&collection).expect("saving changes");
+
+    let loaded = open(&dir).load().expect("loading collection");
+    let titles: Vec<&str> = loaded.decks.iter().map(|deck| deck.title.as_str()).collect();
+    assert_eq!(titles, ["French", "German"]);
+    assert_eq!(questions(&loaded), ["une", "deux"]);
+}
@@ -0,0 +1,123 @@
+//! Deleting decks and cards into the trash, restoring them from it and purging
+//! them for good.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use uuid::Uuid;
+
+use ranki::collection::Collection;
+
+// Spanish and French decks of two cards each, with the first Spanish card
+// answered so there's a schedule to keep
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    collection.add_deck("French");
+    let cards = [(0, "uno"), (0, "dos"), (1, "un"), (1, "deux")];
+    for (deck_id, question) in cards {
+        collection
+            .add_card(deck_id, question.to_string(), "-".to_string(), "".to_string())
+            .expect("adding card");
+    }
+    let uid = collection.decks[0].cards[0].uid;
+    collection
+        .process_results(vec![(uid, Some(true))])
+        .expect("answering card");
+    collection
+}
+
+fn questions(collection: &Collection, deck_id: u32) -> Vec<&str> {
+    let pos = collection.deck_pos_by_id(deck_id).expect("deck is there");
+    collection.decks[pos].cards.iter().map(|card| card.question.as_str()).collect()
+}
+
+#[test]
+fn trashed_cards_are_restored_with_their_schedule() {
+    let mut collection = sample();
+    let card = collection.decks[0].cards[0].clone();
+    collection.trash_card(card.uid).expect("trashing card");
+    assert_eq!(questions(&collection, 0), ["dos"]);
+    assert_eq!(collection.trash.cards[0].deck_title, "Spanish");
+    assert!(collection.card_by_uid(card.uid).is_none());
+
+    collection.restore_card(card.uid).expect("restoring card");
+    assert!(collection.trash.cards.is_empty());
+    let (deck, restored) = collection.card_by_uid(card.uid).expect("card is back");
+    assert_eq!(deck.id, 0);
+    assert_eq!(restored.id, card.id);
+    assert_eq!(restored.due(), card.due());
+}
+
+#[test]
+fn trashed_decks_are_restored_with_their_cards() {
+    let mut collection = sample();
+    let uid = collection.decks[1].uid;
+    collection.trash_deck(1).expect("trashing deck");
+    assert!(collection.deck_pos_by_id(1).is_none());
+    assert!(collection.deck_by_uid(uid).is_none());
+
+    collection.restore_deck(1).expect("restoring deck");
+    assert!(collection.trash.decks.is_empty());
+    assert_eq!(questions(&collection, 1), ["un", "deux"]);
+    assert_eq!(collection.deck_by_uid(uid).expect("deck is back").title, "French");
+}
+
+#[test]
+fn cards_wait_for_their_deck_to_be_restored() {
+    let mut collection = sample();
+    let uid = collection.decks[1].cards[0].uid;
+    collection.trash_card(uid).expect("trashing card");
+    collection.trash_deck(1).expect("trashing deck");
+
+    assert!(collection.restore_card(uid).is_err());
+    assert_eq!(collection.trash.cards.len(), 1);
+    collection.restore_deck(1).expect("restoring deck");
+    collection.restore_card(uid).expect("restoring card");
+    assert_eq!(questions(&collection, 1), ["un", "deux"]);
+}
+
+#[test]
+fn purged_items_are_gone_for_good() {
+    let mut collection = sample();
+    let uid = collection.decks[0].cards[1].uid;
+    collection.trash_card(uid).expect("trashing card");
+    collection.trash_deck(1).expect("trashing deck");
+
+    collection.purge_card(uid).expect("purging card");
+    collection.purge_deck(1).expect("purging deck");
+    assert!(collection.trash.cards.is_empty());
+    assert!(collection.trash.decks.is_empty());
+    assert!(collection.restore_card(uid).is_err());
+    assert!(collection.restore_deck(1).is_err());
+    assert!(collection.purge_card(Uuid::new_v4()).is_err());
+}
+
+#[test]
+fn emptying_the_trash_purges_everything() {
+    let mut collection = sample();
+    let uid = collection.decks[0].cards[1].uid;
+    collection.trash_card(uid).expect("trashing card");
+    collection.trash_deck(1).expect("trashing deck");
+
+    collection.empty_trash();
+    assert!(collection.trash.cards.is_empty());
+    assert!(collection.trash.decks.is_empty());
+    assert_eq!(questions(&collection, 0), ["uno"]);
+}
+
+#[test]
+fn only_expired_items_are_purged() {
+    let mut collection = sample();
+    let uid = collection.decks[0].cards[1].uid;
+    collection.trash_card(uid).expect("trashing card");
+    collection.trash_deck(1).expect("trashing deck");
+    assert!(!collection.purge_expired_trash());
+
+    let trash = Arc::make_mut(&mut collection.trash);
+    let days = trash.retention_days as u64 + 1;
+    trash.decks[0].deleted = SystemTime::now() - Duration::from_secs(days * 86400);
+    assert!(collection.purge_expired_trash());
+    assert!(collection.trash.decks.is_empty());
+    assert_eq!(collection.trash.cards.len(), 1);
+}
@@ -0,0 +1,79 @@
+//! Cards are referred to by uid wherever they are, so they can be edited
+//! while a filtered deck has borrowed them or brought back from the trash.
+
+use ranki::collection::{Collection, Query};
+
+// A Spanish deck of two cards, both lent to a Cram filtered deck
+fn sample() -> Collection {
+    let mut collection = Collection::new();
+    collection.add_deck("Spanish");
+    for (question, answer) in [("uno", "one"), ("dos", "two")] {
+        collection
+            .add_card(0, question.to_string(), answer.to_string(), "".to_string())
+            .expect("adding card");
+    }
+    collection
+        .add_filtered_deck("Cram", "deck:Spanish", true)
+        .expect("adding filtered deck");
+    collection
+}
+
+#[test]
+fn borrowed_cards_are_edited_by_uid() {
+    let mut collection = sample();
+    assert!(collection.decks[0].cards.is_empty());
+    let uid = collection.decks[1].cards[0].uid;
+
+    collection
+        .edit_card(uid, "uno!".to_string(), "one".to_string(), "".to_string())
+        .expect("editing borrowed card");
+    collection
+        .set_tags(uid, vec!["numbers".to_string()])
+        .expect("tagging borrowed card");
+    collection.empty_filtered_deck(1).expect("emptying filtered deck");
+
+    let (deck, card) = collection.card_by_uid(uid).expect("card is back home");
+    assert_eq!(deck.title, "Spanish");
+    assert_eq!(card.question, "uno!");
+    assert_eq!(card.tags, ["numbers"]);
+}
+
+#[test]
+fn quiz_results_find_borrowed_cards() {
+    let mut collection = sample();
+    let quiz = collection.get_quiz(Some(&[1]));
+    assert_eq!(quiz.len(), 2);
+
+    let results = quiz.iter().map(|card| (card.uid, Some(true))).collect();
+    collection.process_results(results).expect("processing results");
+    assert_eq!(collection.due_count(1), 0);
+}
+
+#[test]
+fn trashed_cards_are_restored_by_uid() {
+    let mut collection = sample();
+    collection.empty_filtered_deck(1).expect("emptying filtered deck");
+    let uid = collection.decks[0].cards[1].uid;
+
+    collection.trash_card(uid).expect("trashing card");
+    assert!(collection.card_by_uid(uid).is_none());
+    collection.restore_card(uid).expect("restoring card");
+    let (deck, card) = collection.card_by_uid(uid).expect("card is restored");
+    assert_eq!(deck.title, "Spanish");
+    assert_eq!(card.question, "dos");
+}
+
+#[test]
+fn id_search_matches_cards_and_notes() {
+    let collection = sample();
+    let card = &collection.decks[1].cards[1];
+    for uid in [card.uid, card.note_uid] {
+        let query = Query::parse(&format!("id:{}", uid)).expect("valid query");
+        let found: Vec<&str> = collection
+            .search(&query)
+            .iter()
+            .map(|(_, card)| card.question.as_str())
+            .collect();
+        assert_eq!(found, ["dos"]);
+    }
+}